@@ -1,7 +1,7 @@
-use std::cmp::{max, min};
-use std::time::{Duration, Instant};
 use rust_trees::avl::AVL;
 use rust_trees::rb::RedBlack;
+use std::cmp::{max, min};
+use std::time::{Duration, Instant};
 
 fn main() {
     search_benchmark();
@@ -19,12 +19,12 @@ fn search_benchmark() {
         for i in 0..count {
             avl_tree.insert(i, i);
         }
-        run_test("AVL", count, || { || { avl_search_test(count, &avl_tree) } });
+        run_test("AVL", count, || || avl_search_test(count, &avl_tree));
         let mut rb_tree = RedBlack::new();
         for i in 0..count {
             rb_tree.insert(i, i);
         }
-        run_test("RedBlack", count, || { || { rb_search_test(count, &rb_tree) } });
+        run_test("RedBlack", count, || || rb_search_test(count, &rb_tree));
     }
     print_test_footer();
 }
@@ -32,9 +32,8 @@ fn search_benchmark() {
 fn insertion_benchmark() {
     print_test_header("Creation of tree");
     for count in TEST_NODE_COUNTS {
-
-        run_test("AVL", count, || { || { avl_insert_test(count) } });
-        run_test("RedBlack", count, || { || { rb_insert_test(count) } });
+        run_test("AVL", count, || || avl_insert_test(count));
+        run_test("RedBlack", count, || || rb_insert_test(count));
     }
     print_test_footer();
 }
@@ -53,7 +52,6 @@ fn avl_insert_test(count: u64) {
     }
 }
 
-
 fn rb_search_test(count: u64, tree: &RedBlack<u64, u64>) {
     for i in 0..count {
         tree.find(&i);
@@ -66,7 +64,6 @@ fn avl_search_test(count: u64, tree: &AVL<u64, u64>) {
     }
 }
 
-
 fn rb_deletion_test(count: u64, mut tree: RedBlack<u64, u64>) {
     for i in 0..count {
         tree.remove(&i);
@@ -74,7 +71,7 @@ fn rb_deletion_test(count: u64, mut tree: RedBlack<u64, u64>) {
 }
 
 fn avl_deletion_test(count: u64, mut tree: AVL<u64, u64>) {
-    for i in (0..count) {
+    for i in 0..count {
         tree.remove(&i);
     }
 }
@@ -87,16 +84,14 @@ fn deletion_benchmark() {
             for i in 0..count {
                 avl_tree.insert(i, i);
             }
-            || { avl_deletion_test(count, avl_tree) }
+            || avl_deletion_test(count, avl_tree)
         });
         run_test("RedBlack", count, || {
             let mut rb_tree = RedBlack::new();
             for i in 0..count {
                 rb_tree.insert(i, i);
             }
-            || {
-                rb_deletion_test(count, rb_tree)
-            }
+            || rb_deletion_test(count, rb_tree)
         });
     }
     print_test_footer();
@@ -106,9 +101,12 @@ fn print_test_header(name: &str) {
     println!("--------------------------{name}---------------------------------------")
 }
 
-fn run_test<K: FnOnce() -> (), F: Fn() -> K>(name: &str, nodes: u64, action: F) {
+fn run_test<K: FnOnce(), F: Fn() -> K>(name: &str, nodes: u64, action: F) {
     let (avg, min, max) = timed(action);
-    println!("{:10}-nodes: {:>8} avg: {:>14}us, min: {:>14}us, max: {:>14}us", name, nodes, avg, min, max);
+    println!(
+        "{:10}-nodes: {:>8} avg: {:>14}us, min: {:>14}us, max: {:>14}us",
+        name, nodes, avg, min, max
+    );
 }
 
 fn print_test_footer() {
@@ -120,7 +118,7 @@ fn nanos(duration: Duration) -> u64 {
 }
 
 #[inline]
-fn timed<K: FnOnce() -> (), F: Fn() -> K>(action: F) -> (u64, u64, u64) {
+fn timed<K: FnOnce(), F: Fn() -> K>(action: F) -> (u64, u64, u64) {
     let mut max_time = 0;
     let mut min_time = u64::MAX;
     let mut sum = 0;
@@ -134,5 +132,5 @@ fn timed<K: FnOnce() -> (), F: Fn() -> K>(action: F) -> (u64, u64, u64) {
         min_time = min(current, min_time);
         sum += current;
     }
-    return (sum / ITERATIONS, min_time, max_time);
-}
\ No newline at end of file
+    (sum / ITERATIONS, min_time, max_time)
+}
@@ -2,7 +2,8 @@ use std::cmp::{Ordering};
 use std::fmt::Debug;
 use std::mem;
 use std::ops::{Neg};
-use super::Side;
+use super::{Balancing, Side, TreeStats};
+use super::rb::RedBlack;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum HeightChange {
@@ -23,6 +24,7 @@ fn new_node<K: Ord, V>(key: K, value: V) -> Child<K, V> {
         value,
         left_child: None,
         right_child: None,
+        size: 1,
         metadata: 0,
     }))
 }
@@ -35,33 +37,65 @@ fn abs<V: Neg<Output=V> + PartialOrd<V> + Copy>(value: V) -> V {
 }
 
 impl<K: Ord, V> Node<K, V> {
-    fn pop_smallest_node(mut node: BoxedNode<K, V>) -> (Child<K, V>, BoxedNode<K, V>, HeightChange) {
-        match node.left_child.take() {
+    fn max_abs_factor(&self) -> i8 {
+        let child_factor = |child: &Child<K, V>| child.as_ref().map_or(0, |child| child.max_abs_factor());
+        abs(self.metadata).max(child_factor(&self.left_child)).max(child_factor(&self.right_child))
+    }
+
+    // checks stored balance factors against actual heights of subtrees, returns height of subtree in levels
+    fn checked_height(node: &Child<K, V>) -> Result<usize, String> {
+        let Some(node) = node else {
+            return Ok(0);
+        };
+        let left = Self::checked_height(&node.left_child)?;
+        let right = Self::checked_height(&node.right_child)?;
+        if node.metadata as isize != right as isize - left as isize {
+            return Err(format!("balance factor {} does not match heights of subtrees {} and {}", node.metadata, left, right));
+        }
+        if abs(node.metadata) > 1 {
+            return Err(format!("balance factor {} is outside of range from -1 to 1", node.metadata));
+        }
+        Ok(left.max(right) + 1)
+    }
+
+    // pops the smallest node for left side and the largest node for right side
+    fn pop_outermost_node(mut node: BoxedNode<K, V>, side: Side) -> (Child<K, V>, BoxedNode<K, V>, HeightChange) {
+        let (outer_child, inner_child) = match side {
+            Side::Left => (&mut node.left_child, &mut node.right_child),
+            Side::Right => (&mut node.right_child, &mut node.left_child),
+        };
+        match outer_child.take() {
             // cannot continue, return current node
             None => {
-                // there can be some nodes in right subtree, so we must return them
-                let right_child = node.right_child.take();
-                (right_child, node, HeightChange::Decreased)
+                // there can be some nodes in inner subtree, so we must return them
+                let inner_child = inner_child.take();
+                (inner_child, node, HeightChange::Decreased)
             }
-            // node has left child and therefore is not smallest
+            // node has outer child and therefore is not outermost
             Some(child) => {
-                let (left, popped, mut height_change) = Self::pop_smallest_node(child);
-                node.left_child = left;
+                let (outer, popped, mut height_change) = Self::pop_outermost_node(child, side);
+                *outer_child = outer;
+                node.update_size();
                 // removing child in subtree could have affected height, we must check AVL rules
-                height_change = node.handle_child_change(height_change, Side::Left);
+                height_change = node.handle_child_change(height_change, side);
                 (Some(node), popped, height_change)
             }
         }
     }
 
-    fn remove(mut self, key: &K) -> (Child<K, V>, HeightChange, Option<(K, V)>) {
+    // removes node with given key, if its value satisfies predicate
+    fn remove<F: FnOnce(&V) -> bool>(mut self, key: &K, predicate: F) -> (Child<K, V>, HeightChange, Option<(K, V)>) {
         match key.cmp(&self.key) {
             Ordering::Equal => {
+                if !predicate(&self.value) {
+                    return (Some(Box::new(self)), HeightChange::Unchanged, None);
+                }
                 let (has_left_child, has_right_child) = (self.left_child.is_some(), self.right_child.is_some());
                 // node has 2 children, we can replace current node with next node in inorder succession
                 if has_right_child && has_left_child {
-                    let (right_child, mut replacement, mut change) = Self::pop_smallest_node(self.right_child.unwrap());
+                    let (right_child, mut replacement, mut change) = Self::pop_outermost_node(self.right_child.unwrap(), Side::Left);
                     self.right_child = right_child;
+                    self.update_size();
 
                     mem::swap(&mut replacement.key, &mut self.key);
                     mem::swap(&mut replacement.value, &mut self.value);
@@ -71,21 +105,22 @@ impl<K: Ord, V> Node<K, V> {
                 }
                 // node has one child, we can replace current node with it
                 else if has_right_child {
-                    return (Some(self.right_child.take().unwrap()), HeightChange::Decreased, Some((self.key, self.value)));
+                    (Some(self.right_child.take().unwrap()), HeightChange::Decreased, Some((self.key, self.value)))
                 } else if has_left_child {
-                    return (Some(self.left_child.take().unwrap()), HeightChange::Decreased, Some((self.key, self.value)));
+                    (Some(self.left_child.take().unwrap()), HeightChange::Decreased, Some((self.key, self.value)))
                 }
                 // node has no children,
                 else {
-                    return (None, HeightChange::Decreased, Some((self.key, self.value)));
+                    (None, HeightChange::Decreased, Some((self.key, self.value)))
                 }
             }
             // value is not in current node, we will search it in corresponding child if it exists
             Ordering::Greater => {
                 match self.right_child.take() {
                     Some(child) => {
-                        let (child, change, value) = child.remove(key);
+                        let (child, change, value) = child.remove(key, predicate);
                         self.right_child = child;
+                        self.update_size();
                         let change = self.handle_child_change(change, Side::Right);
                         (Some(Box::new(self)), change, value)
                     }
@@ -97,8 +132,9 @@ impl<K: Ord, V> Node<K, V> {
             Ordering::Less => {
                 match self.left_child.take() {
                     Some(child) => {
-                        let (child, change, value) = child.remove(key);
+                        let (child, change, value) = child.remove(key, predicate);
                         self.left_child = child;
+                        self.update_size();
                         let change = self.handle_child_change(change, Side::Left);
                         (Some(Box::new(self)), change, value)
                     }
@@ -142,6 +178,7 @@ impl<K: Ord, V> Node<K, V> {
                 }
             }
         }
+        self.update_size();
 
         self.handle_child_change(affected_child_change, affected_child_side)
     }
@@ -160,6 +197,7 @@ impl<K: Ord, V> Node<K, V> {
                     Side::Left => -1,
                     Side::Right => 1,
                 };
+                debug_assert!(abs(self.metadata) <= 2, "balance factor {} out of range", self.metadata);
                 // other subtree was higher than affected one, height change made their height same
                 if self.metadata == 0 {
                     return HeightChange::Unchanged;
@@ -174,6 +212,7 @@ impl<K: Ord, V> Node<K, V> {
                     Side::Left => 1,
                     Side::Right => -1,
                 };
+                debug_assert!(abs(self.metadata) <= 2, "balance factor {} out of range", self.metadata);
                 // subtree was higher than affected one, height change made their height same but also decreased height of tree
                 if self.metadata == 0 {
                     return HeightChange::Decreased;
@@ -186,6 +225,7 @@ impl<K: Ord, V> Node<K, V> {
         }
         // balance factor is |2|, tree must be rebalanced
         self.balance();
+        debug_assert!(abs(self.metadata) <= 1, "balance factor {} after rotation", self.metadata);
         if affected_child_change == HeightChange::Decreased && self.metadata == 0 {
             HeightChange::Decreased
         } else {
@@ -199,7 +239,7 @@ impl<K: Ord, V> Node<K, V> {
         if self.metadata == -2 {
             if self.left_child.as_ref().unwrap().metadata <= 0 {
                 // simple rotation to the right is enough
-                self.rotate_right();
+                self.rotate_right_updating_factors();
             } else {
                 // we don't know if LL child exists, more complex rotation is needed
                 self.rotate_left_right();
@@ -209,7 +249,7 @@ impl<K: Ord, V> Node<K, V> {
         else if self.metadata == 2 {
             if self.right_child.as_ref().unwrap().metadata >= 0 {
                 // simple rotation to the left is enough
-                self.rotate_left();
+                self.rotate_left_updating_factors();
             } else {
                 // we don't know if RR child exists, more complex rotation is needed
                 self.rotate_right_left();
@@ -224,32 +264,13 @@ impl<K: Ord, V> Node<K, V> {
     //    / \             / \
     //   W   b           a   b
     //      / \    =>   / \ / \
-    //     c   X       X  Y Z  W
+    //     c   X       W  Y Z  X
     //    / \
     //   Y   Z
     fn rotate_right_left(&mut self) {
-        let mut right = self.right_child.take().unwrap(); // b
-        let mut new_root = right.left_child.take().unwrap(); // c
-        right.left_child = new_root.right_child.take(); // reassign Z
-        self.right_child = new_root.left_child.take(); // reassign Y
-
-        mem::swap(self, &mut new_root); // c is now root and a new_root
-        self.left_child = Some(new_root); // reassign a to c
-        self.right_child = Some(right); // reassign b to c
-
-        // if c was not balanced we must reflect it new parents of Y, Z
-        // from properties of AVL tree we know that height of W, X and Y XOR Z are same
-        let (mut left_child_balance_factor, mut right_child_balance_factor) = (0, 0);
-        if self.metadata == 1 {
-            // Z > Y => Z > W => balance of a is -1
-            left_child_balance_factor = -1;
-        } else if self.metadata == -1 {
-            // Y > Z => Y > X => balance of b is 1
-            right_child_balance_factor = 1;
-        }
-        self.right_child.as_mut().unwrap().metadata = right_child_balance_factor;
-        self.left_child.as_mut().unwrap().metadata = left_child_balance_factor;
-        self.metadata = 0;
+        self.right_child.as_mut().unwrap().rotate_right();
+        self.rotate_left();
+        self.update_factors_after_double_rotation();
     }
 
     // Rotates left-heavy tree with right-leaning left child
@@ -261,25 +282,23 @@ impl<K: Ord, V> Node<K, V> {
     //       / \
     //      Y   Z
     fn rotate_left_right(&mut self) {
-        let mut left = self.left_child.take().unwrap(); // b
-        let mut new_root = left.right_child.take().unwrap(); // c
-        self.left_child = new_root.right_child.take(); // reassign Z
-        left.right_child = new_root.left_child.take(); // reassign Y
-
-        mem::swap(self, &mut new_root); // c is now root and a new_root
-        self.right_child = Some(new_root); // reassign a to c
-        self.left_child = Some(left); // reassign b to c
+        self.left_child.as_mut().unwrap().rotate_left();
+        self.rotate_right();
+        self.update_factors_after_double_rotation();
+    }
 
+    // self is c from double rotation diagrams
+    fn update_factors_after_double_rotation(&mut self) {
         // if c was not balanced we must reflect it new parents of Y, Z
         // from properties of AVL tree we know that height of W, X and Y XOR Z are same
         let (mut left_child_balance_factor, mut right_child_balance_factor) = (0, 0);
-
         if self.metadata == 1 {
+            // Z > Y => left child has higher left subtree
             left_child_balance_factor = -1;
         } else if self.metadata == -1 {
+            // Y > Z => right child has higher right subtree
             right_child_balance_factor = 1;
         }
-
         self.right_child.as_mut().unwrap().metadata = right_child_balance_factor;
         self.left_child.as_mut().unwrap().metadata = left_child_balance_factor;
         self.metadata = 0;
@@ -291,21 +310,18 @@ impl<K: Ord, V> Node<K, V> {
     //      X   b            a   Y
     //         / \    =>    / \
     //        Z   Y        X  Z
-    fn rotate_left(&mut self) {
-        let mut new_root = self.right_child.take().unwrap();
-        self.right_child = new_root.left_child.take();
-        if new_root.metadata == 0 {
+    fn rotate_left_updating_factors(&mut self) {
+        self.rotate_left();
+        let (new_root_factor, previous_root_factor) = if self.metadata == 0 {
             // height of Z and Y is same => a will be right-leaning and b left-leaning
-            new_root.metadata = -1;
-            self.metadata = 1;
+            (-1, 1)
         } else {
-            // height of Y = 1 + height of Z (guaranteed by AVL tree properties and check before calling rotate_right)
-            // => W and Y have same height => b and a are balanced
-            new_root.metadata = 0;
-            self.metadata = 0;
-        }
-        mem::swap(self, &mut new_root);
-        self.left_child = Some(new_root);
+            // height of Y = 1 + height of Z (guaranteed by AVL tree properties and check before rotation)
+            // => X and Z have same height => b and a are balanced
+            (0, 0)
+        };
+        self.metadata = new_root_factor;
+        self.left_child.as_mut().unwrap().metadata = previous_root_factor;
     }
 
     // Rotates left-heavy tree with balanced or left-leaning left child
@@ -314,21 +330,18 @@ impl<K: Ord, V> Node<K, V> {
     //      b   X        Z   a
     //     / \      =>      / \
     //    Z   Y            Y   X
-    fn rotate_right(&mut self) {
-        let mut new_root = self.left_child.take().unwrap(); // b
-        self.left_child = new_root.right_child.take(); // assign Y to a
-        if new_root.metadata == 0 {
+    fn rotate_right_updating_factors(&mut self) {
+        self.rotate_right();
+        let (new_root_factor, previous_root_factor) = if self.metadata == 0 {
             // height of Z and Y is same => a will be left-leaning and b right-leaning
-            new_root.metadata = 1;
-            self.metadata = -1;
+            (1, -1)
         } else {
-            // height of Z = 1 + height of Y (guaranteed by AVL tree properties and check before calling rotate_right)
-            // => W and Y have same height => b and a are balanced
-            new_root.metadata = 0;
-            self.metadata = 0;
-        }
-        mem::swap(self, &mut new_root); // switch a and b
-        self.right_child = Some(new_root); // assign a to b
+            // height of Z = 1 + height of Y (guaranteed by AVL tree properties and check before rotation)
+            // => Y and X have same height => b and a are balanced
+            (0, 0)
+        };
+        self.metadata = new_root_factor;
+        self.right_child.as_mut().unwrap().metadata = previous_root_factor;
     }
 }
 
@@ -336,21 +349,28 @@ pub type AVL<K, V> = super::Tree<K, V, i8>;
 
 impl<K: Ord, V> AVL<K, V> {
     pub fn new() -> Self {
-        return AVL { root: None };
+        Self::new_empty()
     }
 
     pub fn remove(&mut self, key: &K) -> Option<(K, V)> {
+        self.remove_if(key, |_| true)
+    }
+
+    /// Removes node with given key only if its value satisfies predicate.
+    pub fn remove_if<F: FnOnce(&V) -> bool>(&mut self, key: &K, predicate: F) -> Option<(K, V)> {
+        self.assert_not_frozen();
         match self.root.take() {
             None => { None }
             Some(node) => {
                 let returned_value;
-                (self.root, _, returned_value) = node.remove(key);
+                (self.root, _, returned_value) = node.remove(key, predicate);
                 returned_value
             }
         }
     }
 
     pub fn insert(&mut self, key: K, value: V) {
+        self.assert_not_frozen();
         match &mut self.root {
             None => {
                 self.root = new_node(key, value);
@@ -360,6 +380,57 @@ impl<K: Ord, V> AVL<K, V> {
             }
         }
     }
+
+    fn pop_outermost(&mut self, side: Side) -> Option<(K, V)> {
+        self.assert_not_frozen();
+        let (root, popped, _) = Node::pop_outermost_node(self.root.take()?, side);
+        self.root = root;
+        Some((popped.key, popped.value))
+    }
+
+    /// Converts the tree into balanced red-black tree with same entries in linear time.
+    pub fn into_red_black(self) -> RedBlack<K, V> {
+        self.into_strategy()
+    }
+
+    /// Returns diagnostics of the tree, balance is the largest absolute balance factor of any node.
+    pub fn stats(&self) -> TreeStats<K, i8>
+    where
+        K: Clone,
+    {
+        self.stats_with(self.root.as_ref().map_or(0, |node| node.max_abs_factor()))
+    }
+
+    /// Returns balance factor of node with given key, positive when right subtree is higher.
+    pub fn balance_factor_of(&self, key: &K) -> Option<i8> {
+        self.metadata_of(key).copied()
+    }
+}
+
+impl<K: Ord, V> Balancing<K, V> for i8 {
+    fn insert(tree: &mut AVL<K, V>, key: K, value: V) {
+        tree.insert(key, value)
+    }
+
+    fn remove(tree: &mut AVL<K, V>, key: &K) -> Option<(K, V)> {
+        tree.remove(key)
+    }
+
+    fn pop_first(tree: &mut AVL<K, V>) -> Option<(K, V)> {
+        tree.pop_outermost(Side::Left)
+    }
+
+    fn pop_last(tree: &mut AVL<K, V>) -> Option<(K, V)> {
+        tree.pop_outermost(Side::Right)
+    }
+
+    fn balanced_metadata(_: usize, _: usize, left_height: usize, right_height: usize) -> Self {
+        (right_height as isize - left_height as isize) as i8
+    }
+
+    fn check_invariants(tree: &AVL<K, V>) -> Result<(), String> {
+        Node::checked_height(&tree.root).map(|_| ())
+    }
 }
 
 #[cfg(test)]
@@ -367,36 +438,220 @@ mod tests {
     use std::cmp::max;
     use super::*;
 
+    #[test]
+    fn test_conversion_to_red_black_and_back() {
+        let mut tree = AVL::new();
+        for i in 0..300u32 {
+            tree.insert((i * 7919) % 1000, i);
+        }
+        let expected: Vec<(u32, u32)> = tree.iter().map(|(key, value)| (*key, *value)).collect();
+        let mut red_black = tree.into_red_black();
+        assert!(red_black.iter().map(|(key, value)| (*key, *value)).eq(expected.iter().copied()));
+        red_black.insert(1000, 0);
+        red_black.remove(&1000);
+        let tree = red_black.into_avl();
+        check_tree(tree.root.as_ref().unwrap(), 300);
+        assert!(tree.iter().map(|(key, value)| (*key, *value)).eq(expected));
+    }
+
+    #[test]
+    fn test_conversion_keeps_frozen_flag() {
+        let mut tree = AVL::new();
+        tree.insert(1, ());
+        tree.freeze();
+        let red_black = tree.into_red_black();
+        assert!(red_black.is_frozen());
+        assert!(red_black.into_avl().is_frozen());
+        assert!(!AVL::<u32, ()>::new().into_red_black().is_frozen());
+    }
+
+    #[test]
+    fn test_stats_of_small_tree() {
+        let mut tree = AVL::new();
+        assert_eq!(TreeStats { len: 0, height: 0, min: None, max: None, balance: 0 }, tree.stats());
+        for key in [1, 2, 3, 4] {
+            tree.insert(key, ());
+        }
+        assert_eq!(TreeStats { len: 4, height: 2, min: Some(1), max: Some(4), balance: 1 }, tree.stats());
+    }
+
+    #[test]
+    fn test_is_valid_checks_balance_factors() {
+        let mut tree = AVL::new();
+        for key in 0..100 {
+            tree.insert(key, ());
+            assert!(tree.is_valid());
+        }
+        tree.root.as_mut().unwrap().metadata = -1;
+        assert!(!tree.is_valid());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "balance factor 2 is outside of range")]
+    fn test_assert_invariants_names_balance_factor() {
+        let mut tree = AVL::new();
+        tree.insert(1, ());
+        tree.insert(2, ());
+        tree.assert_invariants();
+        // plant right-right chain, which would be rotated by insert
+        tree.root.as_mut().unwrap().right_child.as_mut().unwrap().right_child = new_node(3, ());
+        tree.root.as_mut().unwrap().right_child.as_mut().unwrap().metadata = 1;
+        tree.root.as_mut().unwrap().right_child.as_mut().unwrap().size = 2;
+        tree.root.as_mut().unwrap().metadata = 2;
+        tree.root.as_mut().unwrap().size = 3;
+        tree.assert_invariants();
+    }
+
+    #[test]
+    fn test_balance_factor_of_known_sequence() {
+        let mut tree = AVL::new();
+        for key in [1, 2, 3, 4] {
+            tree.insert(key, ());
+        }
+        // 1, 2, 3 are rotated to have root 2, then 4 is appended under 3
+        assert_eq!(Some(1), tree.balance_factor_of(&2));
+        assert_eq!(Some(0), tree.balance_factor_of(&1));
+        assert_eq!(Some(1), tree.balance_factor_of(&3));
+        assert_eq!(Some(0), tree.balance_factor_of(&4));
+        assert_eq!(None, tree.balance_factor_of(&5));
+
+        tree.insert(0, ());
+        tree.insert(-1, ());
+        // rotation at 1 restores balance of left subtree
+        assert_eq!(Some(0), tree.balance_factor_of(&2));
+        assert_eq!(Some(0), tree.balance_factor_of(&0));
+        assert_eq!(Some(0), tree.balance_factor_of(&1));
+    }
+
     #[test]
     fn test_inserting_and_deleting_keeps_tree_balanced() {
         let vec: Vec<u32> = (0..1000).collect();
         let mut tree = AVL::new();
         for (i, j) in vec.iter().enumerate() {
-            tree.insert(j.clone(), j.clone());
+            tree.insert(*j, *j);
             check_tree(tree.root.as_ref().unwrap(), (i + 1) as u32);
         }
+        assert_eq!(vec.iter().collect::<Vec<&u32>>(), tree.inorder_keys());
         for j in vec.iter() {
             assert_eq!(Some(j), tree.find(j));
         }
         assert!(tree.root.is_some());
         let mut size = vec.len();
         for j in vec.iter() {
-            assert_eq!(Some((j.clone(), j.clone())), tree.remove(j));
+            assert_eq!(Some((*j, *j)), tree.remove(j));
             size -= 1;
             if size > 0 {
                 check_tree(tree.root.as_ref().unwrap(), size as u32);
+                assert_eq!(vec[vec.len() - size..].iter().collect::<Vec<&u32>>(), tree.inorder_keys());
             } else {
                 assert!(tree.root.is_none());
             }
         }
     }
 
-    fn check_tree<K: Ord, V>(tree: &Box<Node<K, V>>, expected_size: u32) {
+    #[test]
+    fn test_remove_if_removes_only_matching_values() {
+        let mut tree = AVL::new();
+        for i in 0..100u32 {
+            tree.insert(i, i % 3);
+        }
+
+        let mut size = 100;
+        for i in 0..100 {
+            let removed = tree.remove_if(&i, |value| *value == 0);
+            if i % 3 == 0 {
+                assert_eq!(Some((i, 0)), removed);
+                size -= 1;
+            } else {
+                assert_eq!(None, removed);
+                assert_eq!(Some(&(i % 3)), tree.find(&i));
+            }
+            check_tree(tree.root.as_ref().unwrap(), size);
+        }
+        assert_eq!(None, tree.remove_if(&100, |_| true));
+    }
+
+    #[test]
+    fn test_pop_first_and_last_keep_tree_balanced() {
+        let mut tree = AVL::new();
+        for i in 0..200u32 {
+            tree.insert(i, i);
+        }
+        let mut size = 200;
+        while size > 2 {
+            assert_eq!(Some((100 - size / 2, 100 - size / 2)), tree.pop_first());
+            assert_eq!(Some((99 + size / 2, 99 + size / 2)), tree.pop_last());
+            size -= 2;
+            check_tree(tree.root.as_ref().unwrap(), size);
+        }
+    }
+
+    #[test]
+    fn test_bulk_insert_keeps_last_value_and_balance() {
+        let mut tree = AVL::new();
+        for i in 0..500u32 {
+            tree.insert(i * 2, 0);
+        }
+        // every key from 0..1000 is three times in shuffled batch
+        tree.bulk_insert((0..3000u32).map(|i| ((i * 7919) % 1000, i)));
+        check_tree(tree.root.as_ref().unwrap(), 1000);
+        for key in 0..1000 {
+            let last = (0..3000).filter(|i| (i * 7919) % 1000 == key).max();
+            assert_eq!(last.as_ref(), tree.find(&key));
+        }
+
+        for size in 1..64 {
+            let mut tree = AVL::new();
+            tree.bulk_insert((0..size).map(|i| (i, i)));
+            check_tree(tree.root.as_ref().unwrap(), size);
+        }
+    }
+
+    #[test]
+    fn test_removal_cascading_rotations_keeps_factors_in_range() {
+        // in Fibonacci tree every inner node is left-leaning, so removing the largest key rotates at every level
+        for height in 1..15 {
+            let mut next_key = 0;
+            let mut tree = AVL { root: fibonacci_tree(height, &mut next_key), frozen: false };
+            let size = next_key;
+            check_tree(tree.root.as_ref().unwrap(), size);
+            assert_eq!(Some((size - 1, ())), tree.pop_last());
+            if size > 1 {
+                check_tree(tree.root.as_ref().unwrap(), size - 1);
+            }
+        }
+
+        let mut next_key = 0;
+        let mut tree = AVL { root: fibonacci_tree(16, &mut next_key), frozen: false };
+        let mut size = next_key;
+        while size > 1 {
+            tree.remove(&(size - 1));
+            size -= 1;
+            check_tree(tree.root.as_ref().unwrap(), size);
+        }
+    }
+
+    fn fibonacci_tree(height: u32, next_key: &mut u32) -> Child<u32, ()> {
+        if height == 0 {
+            return None;
+        }
+        let left_child = fibonacci_tree(height - 1, next_key);
+        let key = *next_key;
+        *next_key += 1;
+        let right_child = fibonacci_tree(height.saturating_sub(2), next_key);
+        let metadata = if height > 1 { -1 } else { 0 };
+        let mut node = Box::new(Node { key, value: (), left_child, right_child, size: 1, metadata });
+        node.update_size();
+        Some(node)
+    }
+
+    fn check_tree<K: Ord, V>(tree: &Node<K, V>, expected_size: u32) {
         let (_, size) = check_balance_factors(tree);
         assert_eq!(expected_size, size);
     }
 
-    fn check_balance_factors<K: Ord, V>(tree: &Box<Node<K, V>>) -> (u32, u32) {
+    fn check_balance_factors<K: Ord, V>(tree: &Node<K, V>) -> (u32, u32) {
         let (left, left_tree_size) = match &tree.left_child {
             None => (0, 0),
             Some(child) => check_balance_factors(child),
@@ -408,6 +663,8 @@ mod tests {
         };
 
         assert_eq!(tree.metadata as i64, right as i64 - left as i64);
+        assert!(abs(tree.metadata) <= 1);
+        assert_eq!(tree.size as u32, 1 + right_tree_size + left_tree_size);
 
         (max(left, right) + 1, 1 + right_tree_size + left_tree_size)
     }
@@ -0,0 +1,96 @@
+use super::{Balancing, Tree};
+
+// Which end of the queue is dropped when capacity is exceeded
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Eviction {
+    Min,
+    Max,
+}
+
+// Ordered map with limited number of entries, see BoundedPQueue::push
+pub struct BoundedPQueue<K: Ord, V, I> {
+    tree: Tree<K, V, I>,
+    capacity: usize,
+    eviction: Eviction,
+}
+
+impl<K: Ord, V, I: Balancing<K, V>> BoundedPQueue<K, V, I> {
+    pub fn new(capacity: usize, eviction: Eviction) -> Self {
+        BoundedPQueue { tree: Tree::new_empty(), capacity, eviction }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Inserts entry, if the queue is full afterwards, entry chosen by eviction policy is removed and returned.
+    /// Pushed entry itself is evicted if its key is outside kept ones.
+    pub fn push(&mut self, key: K, value: V) -> Option<(K, V)> {
+        I::insert(&mut self.tree, key, value);
+        if self.tree.len() <= self.capacity {
+            return None;
+        }
+        match self.eviction {
+            Eviction::Min => self.tree.pop_first(),
+            Eviction::Max => self.tree.pop_last(),
+        }
+    }
+
+    pub fn pop_min(&mut self) -> Option<(K, V)> {
+        self.tree.pop_first()
+    }
+
+    pub fn pop_max(&mut self) -> Option<(K, V)> {
+        self.tree.pop_last()
+    }
+
+    pub fn peek_min(&self) -> Option<(&K, &V)> {
+        self.tree.min()
+    }
+
+    pub fn peek_max(&self) -> Option<(&K, &V)> {
+        self.tree.max()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rb::Color;
+    use super::*;
+
+    #[test]
+    fn test_push_past_capacity_evicts_by_policy() {
+        let mut smallest: BoundedPQueue<u32, char, i8> = BoundedPQueue::new(3, Eviction::Max);
+        let mut largest: BoundedPQueue<u32, char, Color> = BoundedPQueue::new(3, Eviction::Min);
+        for (key, value) in [(5, 'a'), (1, 'b'), (9, 'c')] {
+            assert_eq!(None, smallest.push(key, value));
+            assert_eq!(None, largest.push(key, value));
+        }
+        assert_eq!(Some((9, 'c')), smallest.push(3, 'd'));
+        assert_eq!(Some((1, 'b')), largest.push(3, 'd'));
+        // pushed key outside of kept ones is evicted right away
+        assert_eq!(Some((10, 'e')), smallest.push(10, 'e'));
+        assert_eq!(Some((0, 'f')), largest.push(0, 'f'));
+        // replacing value of existing key does not evict anything
+        assert_eq!(None, smallest.push(5, 'g'));
+        assert_eq!(3, smallest.len());
+
+        assert_eq!(Some((&1, &'b')), smallest.peek_min());
+        assert_eq!(Some((1, 'b')), smallest.pop_min());
+        assert_eq!(Some((5, 'g')), smallest.pop_max());
+        assert_eq!(Some((3, 'd')), smallest.pop_max());
+        assert!(smallest.is_empty());
+
+        assert_eq!(Some((&9, &'c')), largest.peek_max());
+        assert_eq!(Some((3, 'd')), largest.pop_min());
+        assert_eq!(2, largest.len());
+    }
+}
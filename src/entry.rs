@@ -0,0 +1,197 @@
+use std::borrow::Borrow;
+use super::{Balancing, Tree};
+
+// Entry found by borrowed key, see Tree::entry_ref
+pub enum RawEntry<'a, K: Ord, V, I, Q: ?Sized> {
+    Occupied(OccupiedEntry<'a, K, V>),
+    Vacant(RawVacantEntry<'a, K, V, I, Q>),
+}
+
+// Entry for owned key, see Tree::entry
+pub enum Entry<'a, K: Ord, V, I> {
+    Occupied(OccupiedEntry<'a, K, V>),
+    Vacant(VacantEntry<'a, K, V, I>),
+}
+
+pub struct OccupiedEntry<'a, K, V> {
+    pub(crate) key: &'a K,
+    pub(crate) value: &'a mut V,
+}
+
+pub struct RawVacantEntry<'a, K: Ord, V, I, Q: ?Sized> {
+    pub(crate) tree: &'a mut Tree<K, V, I>,
+    pub(crate) key: &'a Q,
+}
+
+pub struct VacantEntry<'a, K: Ord, V, I> {
+    pub(crate) tree: &'a mut Tree<K, V, I>,
+    pub(crate) key: K,
+}
+
+impl<'a, K: Ord, V, I: Balancing<K, V>> Entry<'a, K, V, I> {
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        self.or_insert_with_key(|_| default())
+    }
+
+    /// Returns value of the entry, `default` is called with key of the entry only if entry is vacant.
+    pub fn or_insert_with_key<F: FnOnce(&K) -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let value = default(entry.key());
+                entry.insert(value)
+            }
+        }
+    }
+}
+
+impl<'a, K: Ord + Borrow<Q>, V, I: Balancing<K, V>, Q: Ord + ?Sized> RawEntry<'a, K, V, I, Q> {
+    /// Returns value of the entry, `make` is called to create owned key and value only if entry is vacant.
+    pub fn or_insert_with<F: FnOnce() -> (K, V)>(self, make: F) -> &'a mut V {
+        match self {
+            RawEntry::Occupied(entry) => entry.into_mut(),
+            RawEntry::Vacant(entry) => {
+                let (key, value) = make();
+                entry.insert(key, value)
+            }
+        }
+    }
+}
+
+impl<'a, K, V> OccupiedEntry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        self.key
+    }
+
+    pub fn get(&self) -> &V {
+        self.value
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        self.value
+    }
+
+    pub fn into_mut(self) -> &'a mut V {
+        self.value
+    }
+}
+
+impl<'a, K: Ord + Borrow<Q>, V, I: Balancing<K, V>, Q: Ord + ?Sized> RawVacantEntry<'a, K, V, I, Q> {
+    /// Inserts owned key, which must be equal to the searched one, with given value.
+    /// Panics if the key differs, because value of other key would be returned otherwise.
+    pub fn insert(self, key: K, value: V) -> &'a mut V {
+        assert!(key.borrow() == self.key, "inserted key differs from the searched one");
+        I::insert(self.tree, key, value);
+        // rotations during insertion move nodes around, so inserted value must be looked up again
+        self.tree.find_mut(self.key).unwrap().1
+    }
+}
+
+impl<'a, K: Ord, V, I: Balancing<K, V>> VacantEntry<'a, K, V, I> {
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// Inserts value and returns reference to it. Takes three descents: rank of the key is found first,
+    /// because the key is moved into the tree and rotations move nodes around, then the key is inserted
+    /// and its node is selected by the rank.
+    pub fn insert(self, value: V) -> &'a mut V {
+        // key is moved into the tree and rotations move nodes around,
+        // so inserted node is found by number of smaller keys, which does not change by rotations
+        let rank = self.tree.rank(&self.key);
+        I::insert(self.tree, self.key, value);
+        self.tree.select_mut(rank).unwrap().1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use crate::avl::AVL;
+    use crate::rb::RedBlack;
+    use super::*;
+
+    #[test]
+    fn test_entry_ref_constructs_key_only_when_vacant() {
+        let constructed = Cell::new(0);
+        let make_key = |key: &str| {
+            constructed.set(constructed.get() + 1);
+            key.to_string()
+        };
+
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for key in ["b", "a", "c"] {
+            avl.insert(key.to_string(), 0);
+            rb.insert(key.to_string(), 0);
+        }
+
+        *avl.entry_ref("a").or_insert_with(|| (make_key("a"), 0)) += 1;
+        *rb.entry_ref("a").or_insert_with(|| (make_key("a"), 0)) += 1;
+        assert_eq!(0, constructed.get());
+        assert_eq!(Some(&1), avl.find(&"a".to_string()));
+        assert_eq!(Some(&1), rb.find(&"a".to_string()));
+
+        *avl.entry_ref("d").or_insert_with(|| (make_key("d"), 0)) += 1;
+        *rb.entry_ref("d").or_insert_with(|| (make_key("d"), 0)) += 1;
+        assert_eq!(2, constructed.get());
+        assert_eq!(Some(&1), avl.find(&"d".to_string()));
+        assert_eq!(Some(&1), rb.find(&"d".to_string()));
+
+        match avl.entry_ref("e") {
+            RawEntry::Occupied(_) => panic!("entry should be vacant"),
+            RawEntry::Vacant(entry) => {
+                *entry.insert(make_key("e"), 5) += 1;
+            }
+        }
+        assert_eq!(Some(&6), avl.find(&"e".to_string()));
+    }
+
+    #[test]
+    #[should_panic(expected = "inserted key differs")]
+    fn test_raw_vacant_entry_rejects_different_key() {
+        let mut tree = RedBlack::new();
+        tree.insert("a".to_string(), 0);
+        if let RawEntry::Vacant(entry) = tree.entry_ref("b") {
+            entry.insert("c".to_string(), 1);
+        }
+    }
+
+    #[test]
+    fn test_or_insert_with_key_is_called_only_for_vacant_entry() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for i in 0..100 {
+            avl.insert(i * 2, i);
+            rb.insert(i * 2, i);
+        }
+
+        for key in 0..200 {
+            assert_eq!(key, *avl.entry(key).key());
+            let value = *avl.entry(key).or_insert_with_key(|key| key * 10);
+            assert_eq!(if key % 2 == 0 { key / 2 } else { key * 10 }, value);
+            let value = *rb.entry(key).or_insert_with_key(|key| key * 10);
+            assert_eq!(if key % 2 == 0 { key / 2 } else { key * 10 }, value);
+        }
+
+        *rb.entry(500).or_insert(1) += 1;
+        *rb.entry(500).or_insert(1) += 1;
+        assert_eq!(Some(&3), rb.find(&500));
+        assert_eq!(Some(&10), avl.find(&1));
+    }
+}
@@ -0,0 +1,195 @@
+use std::cmp::Ordering;
+use std::ops::Bound;
+use super::{Child, Node};
+
+pub(crate) fn satisfies_lower_bound<K: Ord>(key: &K, bound: Bound<&K>) -> bool {
+    match bound {
+        Bound::Included(bound) => key.cmp(bound) != Ordering::Less,
+        Bound::Excluded(bound) => key.cmp(bound) == Ordering::Greater,
+        Bound::Unbounded => true,
+    }
+}
+
+pub(crate) fn satisfies_upper_bound<K: Ord>(key: &K, bound: Bound<&K>) -> bool {
+    match bound {
+        Bound::Included(bound) => key.cmp(bound) != Ordering::Greater,
+        Bound::Excluded(bound) => key.cmp(bound) == Ordering::Less,
+        Bound::Unbounded => true,
+    }
+}
+
+// Inorder iterator over borrowed tree, stack contains nodes whose left subtree is being visited
+pub struct Iter<'a, K: Ord, V, I> {
+    stack: Vec<&'a Node<K, V, I>>,
+}
+
+impl<'a, K: Ord, V, I> Iter<'a, K, V, I> {
+    pub(crate) fn new(root: Option<&'a Node<K, V, I>>) -> Self {
+        let mut iter = Iter { stack: Vec::new() };
+        iter.push_left_path(root);
+        iter
+    }
+
+    // creates iterator starting from the smallest key satisfying lower bound
+    pub(crate) fn from_lower_bound(root: Option<&'a Node<K, V, I>>, bound: Bound<&K>) -> Self {
+        let mut iter = Iter { stack: Vec::new() };
+        let mut node = root;
+        while let Some(current) = node {
+            // nodes satisfying bound are visited after their left subtree, others are skipped with their left subtree
+            if satisfies_lower_bound(&current.key, bound) {
+                iter.stack.push(current);
+                node = current.left_child.as_deref();
+            } else {
+                node = current.right_child.as_deref();
+            }
+        }
+        iter
+    }
+
+    fn push_left_path(&mut self, mut node: Option<&'a Node<K, V, I>>) {
+        while let Some(current) = node {
+            self.stack.push(current);
+            node = current.left_child.as_deref();
+        }
+    }
+}
+
+// derived Clone would require Clone for keys, values and metadata, but only references are cloned
+impl<K: Ord, V, I> Clone for Iter<'_, K, V, I> {
+    fn clone(&self) -> Self {
+        Iter { stack: self.stack.clone() }
+    }
+}
+
+impl<'a, K: Ord, V, I> Iterator for Iter<'a, K, V, I> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        // left subtree was already visited, continue with smallest node of right subtree
+        self.push_left_path(node.right_child.as_deref());
+        Some((&node.key, &node.value))
+    }
+}
+
+// Inorder iterator over borrowed tree reporting depth of nodes, stack contains nodes with their depth
+pub struct DepthIter<'a, K: Ord, V, I> {
+    stack: Vec<(&'a Node<K, V, I>, usize)>,
+}
+
+impl<'a, K: Ord, V, I> DepthIter<'a, K, V, I> {
+    pub(crate) fn new(root: Option<&'a Node<K, V, I>>) -> Self {
+        let mut iter = DepthIter { stack: Vec::new() };
+        iter.push_left_path(root, 0);
+        iter
+    }
+
+    fn push_left_path(&mut self, mut node: Option<&'a Node<K, V, I>>, mut depth: usize) {
+        while let Some(current) = node {
+            self.stack.push((current, depth));
+            node = current.left_child.as_deref();
+            depth += 1;
+        }
+    }
+}
+
+impl<'a, K: Ord, V, I> Iterator for DepthIter<'a, K, V, I> {
+    type Item = (usize, &'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (node, depth) = self.stack.pop()?;
+        self.push_left_path(node.right_child.as_deref(), depth + 1);
+        Some((depth, &node.key, &node.value))
+    }
+}
+
+// Inorder iterator consuming the tree, stack contains nodes whose left subtree was already detached
+pub struct IntoIter<K: Ord, V, I> {
+    stack: Vec<Box<Node<K, V, I>>>,
+    remaining: usize,
+}
+
+impl<K: Ord, V, I> IntoIter<K, V, I> {
+    pub(crate) fn new(root: Child<K, V, I>) -> Self {
+        let remaining = Node::size_of(&root);
+        let mut iter = IntoIter { stack: Vec::new(), remaining };
+        iter.push_left_path(root);
+        iter
+    }
+
+    fn push_left_path(&mut self, mut node: Child<K, V, I>) {
+        while let Some(mut current) = node {
+            node = current.left_child.take();
+            self.stack.push(current);
+        }
+    }
+}
+
+impl<K: Ord, V, I> Iterator for IntoIter<K, V, I> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut node = self.stack.pop()?;
+        self.push_left_path(node.right_child.take());
+        self.remaining -= 1;
+        let Node { key, value, .. } = *node;
+        Some((key, value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+// node split into borrowed key, value and right subtree, which allows borrowing values of multiple nodes at once
+type SplitNode<'a, K, V, I> = (&'a K, &'a mut V, Option<&'a mut Node<K, V, I>>);
+
+// Inorder iterator over mutably borrowed range of tree
+pub struct RangeMut<'a, K: Ord, V, I> {
+    stack: Vec<SplitNode<'a, K, V, I>>,
+    remaining: usize, // number of nodes in range, which were not visited yet
+}
+
+impl<'a, K: Ord, V, I> RangeMut<'a, K, V, I> {
+    // creates iterator over count nodes starting from the smallest key satisfying lower bound
+    pub(crate) fn new(root: Option<&'a mut Node<K, V, I>>, bound: Bound<&K>, count: usize) -> Self {
+        let mut iter = RangeMut { stack: Vec::new(), remaining: count };
+        let mut node = root;
+        while let Some(current) = node {
+            let Node { key, value, left_child, right_child, .. } = current;
+            if satisfies_lower_bound(key, bound) {
+                iter.stack.push((key, value, right_child.as_deref_mut()));
+                node = left_child.as_deref_mut();
+            } else {
+                node = right_child.as_deref_mut();
+            }
+        }
+        iter
+    }
+
+    fn push_left_path(&mut self, mut node: Option<&'a mut Node<K, V, I>>) {
+        while let Some(current) = node {
+            let Node { key, value, left_child, right_child, .. } = current;
+            self.stack.push((key, value, right_child.as_deref_mut()));
+            node = left_child.as_deref_mut();
+        }
+    }
+}
+
+impl<'a, K: Ord, V, I> Iterator for RangeMut<'a, K, V, I> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let (key, value, right_child) = self.stack.pop()?;
+        self.push_left_path(right_child);
+        self.remaining -= 1;
+        Some((key, value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
@@ -1,16 +1,36 @@
-use std::cmp::Ordering;
+use std::borrow::Borrow;
+#[cfg(any(test, feature = "rotation-stats"))]
+use std::cell::Cell;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{btree_map, VecDeque};
+use std::error::Error;
+use std::fmt;
+use std::iter::{from_fn, Sum, Take};
+use std::mem;
+use std::ops::{AddAssign, Bound, Neg, Range, RangeBounds, Sub, SubAssign};
+use crate::entry::{Entry, RawEntry};
+use crate::iter::{satisfies_lower_bound, satisfies_upper_bound, DepthIter, IntoIter, Iter, RangeMut};
 
 pub mod rb;
 pub mod avl;
+pub mod entry;
+pub mod iter;
+pub mod bounded;
 
 
 type Child<K, V, I> = Option<Box<Node<K, V, I>>>;
+type EntryRef<'a, K, V> = (&'a K, &'a V);
+type Neighbors<'a, K, V> = (Option<EntryRef<'a, K, V>>, Option<EntryRef<'a, K, V>>);
+
+// guess of bytes used by allocator for bookkeeping of each allocation
+const ALLOCATOR_OVERHEAD: usize = 2 * mem::size_of::<usize>();
 
 struct Node<K: Ord, V, M> {
     key: K,
     value: V,
     left_child: Child<K, V, M>,
     right_child: Child<K, V, M>,
+    size: usize, // number of nodes in subtree, used for order statistics
     metadata: M, // for data used in balancing algorithm
 }
 
@@ -32,6 +52,10 @@ impl Side {
 // BST operations that does not change tree
 impl<K: Ord, V, M> Node<K, V, M> {
     fn find(&self, key: &K) -> Option<&V> {
+        self.find_node(key).map(|node| &node.value)
+    }
+
+    fn find_node(&self, key: &K) -> Option<&Self> {
         let mut root = self;
         loop {
             match key.cmp(&root.key) {
@@ -52,19 +76,278 @@ impl<K: Ord, V, M> Node<K, V, M> {
                     }
                 },
                 Ordering::Equal => {
-                    return Some(&root.value);
+                    return Some(root);
+                }
+            }
+        }
+    }
+
+    fn find_mut<Q: Ord + ?Sized>(&mut self, key: &Q) -> Option<(&K, &mut V)>
+    where
+        K: Borrow<Q>,
+    {
+        let mut root = self;
+        loop {
+            match key.cmp(root.key.borrow()) {
+                Ordering::Less => {
+                    root = root.left_child.as_mut()?;
+                }
+                Ordering::Greater => {
+                    root = root.right_child.as_mut()?;
+                }
+                Ordering::Equal => {
+                    return Some((&root.key, &mut root.value));
+                }
+            }
+        }
+    }
+
+    // keys of nodes visited by search from root to node with given key
+    fn path_to(&self, key: &K) -> Option<Vec<&K>> {
+        let mut root = self;
+        let mut path = vec![&root.key];
+        loop {
+            match key.cmp(&root.key) {
+                Ordering::Less => {
+                    root = root.left_child.as_ref()?;
+                }
+                Ordering::Greater => {
+                    root = root.right_child.as_ref()?;
+                }
+                Ordering::Equal => {
+                    return Some(path);
+                }
+            }
+            path.push(&root.key);
+        }
+    }
+
+    // values of all nodes visited by search for key, the last one belongs to the key or to a leaf
+    fn search_path_values(&self, key: &K) -> Vec<&V> {
+        let mut node = Some(self);
+        let mut values = Vec::new();
+        while let Some(current) = node {
+            values.push(&current.value);
+            node = match key.cmp(&current.key) {
+                Ordering::Less => current.left_child.as_deref(),
+                Ordering::Greater => current.right_child.as_deref(),
+                Ordering::Equal => None,
+            };
+        }
+        values
+    }
+
+    // finds values of two different keys, node where their search paths diverge is split into disjoint borrows
+    fn find_two_mut(&mut self, first: &K, second: &K) -> (Option<&mut V>, Option<&mut V>) {
+        let mut root = self;
+        loop {
+            match (first.cmp(&root.key), second.cmp(&root.key)) {
+                (Ordering::Less, Ordering::Less) => match root.left_child.as_mut() {
+                    None => {
+                        return (None, None);
+                    }
+                    Some(child) => {
+                        root = child;
+                    }
+                },
+                (Ordering::Greater, Ordering::Greater) => match root.right_child.as_mut() {
+                    None => {
+                        return (None, None);
+                    }
+                    Some(child) => {
+                        root = child;
+                    }
+                },
+                (first_order, second_order) => {
+                    let mut value = Some(&mut root.value);
+                    let mut left = root.left_child.as_deref_mut();
+                    let mut right = root.right_child.as_deref_mut();
+                    let first_value = Self::take_split_value(first, first_order, &mut value, &mut left, &mut right);
+                    let second_value = Self::take_split_value(second, second_order, &mut value, &mut left, &mut right);
+                    return (first_value, second_value);
+                }
+            }
+        }
+    }
+
+    fn take_split_value<'a>(
+        key: &K,
+        order: Ordering,
+        value: &mut Option<&'a mut V>,
+        left: &mut Option<&'a mut Self>,
+        right: &mut Option<&'a mut Self>,
+    ) -> Option<&'a mut V> {
+        match order {
+            Ordering::Equal => value.take(),
+            Ordering::Less => left.take().and_then(|node| node.find_mut(key)).map(|(_, value)| value),
+            Ordering::Greater => right.take().and_then(|node| node.find_mut(key)).map(|(_, value)| value),
+        }
+    }
+
+    // number of keys smaller than given key
+    fn rank(&self, key: &K) -> usize {
+        let mut root = Some(self);
+        let mut rank = 0;
+        while let Some(node) = root {
+            match key.cmp(&node.key) {
+                Ordering::Less => {
+                    root = node.left_child.as_deref();
+                }
+                Ordering::Greater => {
+                    // node and its whole left subtree are smaller
+                    rank += 1 + Self::size_of(&node.left_child);
+                    root = node.right_child.as_deref();
+                }
+                Ordering::Equal => {
+                    return rank + Self::size_of(&node.left_child);
+                }
+            }
+        }
+        rank
+    }
+
+    // node with given number of smaller keys
+    fn select(&self, mut rank: usize) -> Option<&Self> {
+        let mut root = self;
+        loop {
+            let left_size = Self::size_of(&root.left_child);
+            match rank.cmp(&left_size) {
+                Ordering::Less => {
+                    root = root.left_child.as_ref()?;
+                }
+                Ordering::Greater => {
+                    rank -= left_size + 1;
+                    root = root.right_child.as_ref()?;
+                }
+                Ordering::Equal => {
+                    return Some(root);
+                }
+            }
+        }
+    }
+
+    // node with given number of smaller keys
+    fn select_mut(&mut self, mut rank: usize) -> Option<(&K, &mut V)> {
+        let mut root = self;
+        loop {
+            let left_size = Self::size_of(&root.left_child);
+            match rank.cmp(&left_size) {
+                Ordering::Less => {
+                    root = root.left_child.as_mut()?;
+                }
+                Ordering::Greater => {
+                    rank -= left_size + 1;
+                    root = root.right_child.as_mut()?;
+                }
+                Ordering::Equal => {
+                    return Some((&root.key, &mut root.value));
+                }
+            }
+        }
+    }
+
+    // strict predecessor and successor of given key, which does not need to be in the tree
+    fn neighbors(&self, key: &K) -> Neighbors<'_, K, V> {
+        let mut root = Some(self);
+        // last nodes where search turned right and left
+        let mut predecessor = None;
+        let mut successor = None;
+        while let Some(node) = root {
+            match key.cmp(&node.key) {
+                Ordering::Less => {
+                    successor = Some((&node.key, &node.value));
+                    root = node.left_child.as_deref();
+                }
+                Ordering::Greater => {
+                    predecessor = Some((&node.key, &node.value));
+                    root = node.right_child.as_deref();
+                }
+                Ordering::Equal => {
+                    if let Some(left_child) = &node.left_child {
+                        predecessor = Some(left_child.max());
+                    }
+                    if let Some(right_child) = &node.right_child {
+                        successor = Some(right_child.min());
+                    }
+                    break;
                 }
             }
         }
+        (predecessor, successor)
+    }
+
+    // same keys on same places with same metadata, values are not compared
+    fn structural_eq(&self, other: &Self) -> bool
+    where
+        M: PartialEq,
+    {
+        let children_eq = |child: &Child<K, V, M>, other: &Child<K, V, M>| match (child, other) {
+            (None, None) => true,
+            (Some(child), Some(other)) => child.structural_eq(other),
+            _ => false,
+        };
+        self.key == other.key
+            && self.metadata == other.metadata
+            && children_eq(&self.left_child, &other.left_child)
+            && children_eq(&self.right_child, &other.right_child)
+    }
+
+    // replaces keys in whole subtree, shape and metadata are kept
+    fn map_keys<L: Ord, F: Fn(K) -> L>(self, f: &F) -> Node<L, V, M> {
+        let Node { key, value, left_child, right_child, size, metadata } = self;
+        Node {
+            left_child: left_child.map(|child| Box::new(child.map_keys(f))),
+            key: f(key),
+            right_child: right_child.map(|child| Box::new(child.map_keys(f))),
+            value,
+            size,
+            metadata,
+        }
+    }
+
+    // number of edges on the longest path down to a leaf
+    fn height(&self) -> usize {
+        let child_height = |child: &Child<K, V, M>| child.as_ref().map_or(0, |child| child.height() + 1);
+        child_height(&self.left_child).max(child_height(&self.right_child))
+    }
+
+    // smallest node satisfying lower bound
+    fn ceiling(&self, bound: Bound<&K>) -> Option<&Self> {
+        let mut root = Some(self);
+        let mut ceiling = None;
+        while let Some(node) = root {
+            if satisfies_lower_bound(&node.key, bound) {
+                ceiling = Some(node);
+                root = node.left_child.as_deref();
+            } else {
+                root = node.right_child.as_deref();
+            }
+        }
+        ceiling
+    }
+
+    // largest node satisfying upper bound
+    fn floor(&self, bound: Bound<&K>) -> Option<&Self> {
+        let mut root = Some(self);
+        let mut floor = None;
+        while let Some(node) = root {
+            if satisfies_upper_bound(&node.key, bound) {
+                floor = Some(node);
+                root = node.right_child.as_deref();
+            } else {
+                root = node.left_child.as_deref();
+            }
+        }
+        floor
     }
 
     fn min(&self) -> (&K, &V) {
         let mut min = self;
-        while let Some(right_child) = &min.right_child {
-            min = right_child;
+        while let Some(left_child) = &min.left_child {
+            min = left_child;
         }
 
-        return (&min.key, &min.value);
+        (&min.key, &min.value)
     }
 
     fn max(&self) -> (&K, &V) {
@@ -73,7 +356,7 @@ impl<K: Ord, V, M> Node<K, V, M> {
             max = right_child;
         }
 
-        return (&max.key, &max.value);
+        (&max.key, &max.value)
     }
 
     // finds smallest node with key larger than given key
@@ -87,7 +370,7 @@ impl<K: Ord, V, M> Node<K, V, M> {
                     match &root.left_child {
                         None => {
                             // current node is leaf, next node is current one
-                            return last_greater.and_then(|node| { Some((&node.key, &node.value)) });
+                            return last_greater.map(|node| (&node.key, &node.value));
                         }
                         Some(child) => {
                             // we take step left in the tree, if next node is given one without right child this is next larger
@@ -98,7 +381,8 @@ impl<K: Ord, V, M> Node<K, V, M> {
                 // searched node is larger so we must look in right subtree
                 Ordering::Greater => match &root.right_child {
                     None => {
-                        return None;
+                        // key is not in the tree, next node is the last one we stepped left from
+                        return last_greater.map(|node| (&node.key, &node.value));
                     }
                     Some(child) => {
                         root = child;
@@ -110,7 +394,7 @@ impl<K: Ord, V, M> Node<K, V, M> {
                         None => {
                             // node has no children with larger nodes, smallest node is last larger one
                             // if last_greater is none, given is largest in the whole tree
-                            last_greater.and_then(|node| { Some((&node.key, &node.value)) })
+                            last_greater.map(|node| (&node.key, &node.value))
                         }
                         Some(right_child) => {
                             // node has children with larger nodes, smallest of them is next node
@@ -123,52 +407,3531 @@ impl<K: Ord, V, M> Node<K, V, M> {
     }
 }
 
-pub struct Tree<K: Ord, V, I> {
-    root: Child<K, V, I>,
+#[cfg(any(test, feature = "rotation-stats"))]
+thread_local! {
+    // number of rotations performed by current thread, read by operations counting rotations
+    static ROTATIONS: Cell<u64> = const { Cell::new(0) };
 }
 
-impl<K: Ord, V, I> Tree<K, V, I> {
-    pub fn find(&self, value: &K) -> Option<&V> {
-        match &self.root {
-            None => {
-                None
-            }
-            Some(node) => {
-                node.find(value)
-            }
+#[cfg(any(test, feature = "rotation-stats"))]
+fn count_rotation() {
+    ROTATIONS.with(|rotations| rotations.set(rotations.get() + 1));
+}
+
+// rotations are counted only with rotation-stats feature, so plain inserts and removals do not touch thread local
+#[cfg(not(any(test, feature = "rotation-stats")))]
+fn count_rotation() {}
+
+// runs operation and adds number of rotations it performed to counter
+#[cfg(any(test, feature = "rotation-stats"))]
+fn counting_rotations<T>(rotations: &mut u64, operation: impl FnOnce() -> T) -> T {
+    let before = ROTATIONS.with(Cell::get);
+    let result = operation();
+    *rotations += ROTATIONS.with(Cell::get) - before;
+    result
+}
+
+// Structural rotations shared by all balancing strategies
+// metadata stay with their nodes, strategies must fix them after rotation
+impl<K: Ord, V, M> Node<K, V, M> {
+    // Rotates tree to the left
+    //        a                b
+    //       / \              / \
+    //      W   b            a   Y
+    //         / \    =>    / \
+    //        Z   Y        W   Z
+    fn rotate_left(&mut self) {
+        count_rotation();
+        let mut new_self = self.right_child.take().unwrap(); // takes b
+        self.right_child = new_self.left_child.take(); // reassign Z
+        mem::swap(self, &mut new_self);
+        new_self.update_size();
+        self.left_child = Some(new_self); // takes a to b
+        self.update_size();
+    }
+
+    // Rotates tree to the right
+    //        a            b
+    //       / \          / \
+    //      b   X        Z   a
+    //     / \      =>      / \
+    //    Z   Y            Y   X
+    fn rotate_right(&mut self) {
+        count_rotation();
+        let mut new_self = self.left_child.take().unwrap(); // takes b
+        self.left_child = new_self.right_child.take(); // reassign Y
+        mem::swap(self, &mut new_self);
+        new_self.update_size();
+        self.right_child = Some(new_self); // takes a to b
+        self.update_size();
+    }
+
+    // lifts child on given side to the root
+    fn rotate_from(&mut self, side: Side) {
+        match side {
+            Side::Left => self.rotate_right(),
+            Side::Right => self.rotate_left()
         }
     }
 
-    pub fn min(&self) -> Option<(&K, &V)> {
-        match &self.root {
-            None => {
-                None
-            }
+    // moves root down to given side
+    fn rotate_to(&mut self, side: Side) {
+        match side {
+            Side::Left => self.rotate_left(),
+            Side::Right => self.rotate_right()
+        }
+    }
+}
+
+// Operations detaching nodes and building trees from them
+impl<K: Ord, V, M> Node<K, V, M> {
+    // must be called whenever children of node change
+    fn update_size(&mut self) {
+        self.size = 1 + Self::size_of(&self.left_child) + Self::size_of(&self.right_child);
+    }
+
+    fn size_of(node: &Child<K, V, M>) -> usize {
+        node.as_ref().map_or(0, |node| node.size)
+    }
+
+    // checks stored sizes of whole subtree, returns actual number of nodes if all of them match
+    fn checked_size(node: &Child<K, V, M>) -> Option<usize> {
+        match node {
+            None => Some(0),
             Some(node) => {
-                Some(node.min())
+                let size = 1 + Self::checked_size(&node.left_child)? + Self::checked_size(&node.right_child)?;
+                (size == node.size).then_some(size)
             }
         }
     }
 
-    pub fn max(&self) -> Option<(&K, &V)> {
-        match &self.root {
-            None => {
-                None
+    // detaches all nodes of subtree and appends them to given vector in inorder succession
+    fn flatten(mut node: Box<Self>, nodes: &mut Vec<Box<Self>>) {
+        if let Some(left) = node.left_child.take() {
+            Self::flatten(left, nodes);
+        }
+        let right = node.right_child.take();
+        nodes.push(node);
+        if let Some(right) = right {
+            Self::flatten(right, nodes);
+        }
+    }
+}
+
+impl<K: Ord, V, M: Balancing<K, V>> Node<K, V, M> {
+    // creates node, which is not part of any tree yet
+    fn detached(key: K, value: V) -> Box<Self> {
+        Box::new(Node {
+            key,
+            value,
+            left_child: None,
+            right_child: None,
+            size: 1,
+            metadata: M::balanced_metadata(0, 0, 0, 0),
+        })
+    }
+
+    // builds tree of minimal height from nodes sorted by key without duplicates
+    fn build_balanced(nodes: Vec<Box<Self>>) -> Child<K, V, M> {
+        let count = nodes.len();
+        if count == 0 {
+            return None;
+        }
+        let last_level = count.ilog2() as usize;
+        Self::build_subtree(&mut nodes.into_iter(), count, 0, last_level).0
+    }
+
+    // takes count nodes from iterator and returns their subtree with its height
+    fn build_subtree(
+        nodes: &mut impl Iterator<Item=Box<Self>>,
+        count: usize,
+        depth: usize,
+        last_level: usize,
+    ) -> (Child<K, V, M>, usize) {
+        if count == 0 {
+            return (None, 0);
+        }
+        // sizes of subtrees differ at most by one, so all levels except the last one are full
+        let left_count = (count - 1) / 2;
+        let (left_child, left_height) = Self::build_subtree(nodes, left_count, depth + 1, last_level);
+        let mut node = nodes.next().unwrap();
+        let (right_child, right_height) = Self::build_subtree(nodes, count - 1 - left_count, depth + 1, last_level);
+
+        node.left_child = left_child;
+        node.right_child = right_child;
+        node.size = count;
+        node.metadata = M::balanced_metadata(depth, last_level, left_height, right_height);
+        (Some(node), left_height.max(right_height) + 1)
+    }
+}
+
+// Balancing strategy of the tree, implemented by type of metadata stored in nodes.
+// Allows methods shared by all trees to alter them.
+pub trait Balancing<K: Ord, V>: Sized {
+    fn insert(tree: &mut Tree<K, V, Self>, key: K, value: V);
+
+    fn remove(tree: &mut Tree<K, V, Self>, key: &K) -> Option<(K, V)>;
+
+    fn pop_first(tree: &mut Tree<K, V, Self>) -> Option<(K, V)>;
+
+    fn pop_last(tree: &mut Tree<K, V, Self>) -> Option<(K, V)>;
+
+    // metadata of node in tree built from sorted nodes, where all levels except the last one are full
+    // heights are counted in levels, so empty subtree has height 0
+    fn balanced_metadata(depth: usize, last_level: usize, left_height: usize, right_height: usize) -> Self;
+
+    // describes the first broken rule of balancing strategy, ordering of keys and sizes are checked by Tree::violation
+    fn check_invariants(tree: &Tree<K, V, Self>) -> Result<(), String>;
+}
+
+// One page of entries returned by Tree::page
+#[derive(Debug, PartialEq)]
+pub struct Page<'a, K, V> {
+    pub entries: Vec<(&'a K, &'a V)>,
+    // key to pass as `after` to get following page, None if this page is the last one
+    pub next_after: Option<&'a K>,
+}
+
+// Change turning one tree into other, see Tree::diff, Changed contains key, old value and new value
+#[derive(Debug, Clone, PartialEq)]
+pub enum Diff<K, V> {
+    Added(K, V),
+    Removed(K, V),
+    Changed(K, V, V),
+}
+
+impl<K: Clone, V: Clone> Diff<&K, &V> {
+    /// Clones borrowed keys and values, so the change can be applied to the tree it was computed from.
+    pub fn cloned(self) -> Diff<K, V> {
+        match self {
+            Diff::Added(key, value) => Diff::Added(key.clone(), value.clone()),
+            Diff::Removed(key, value) => Diff::Removed(key.clone(), value.clone()),
+            Diff::Changed(key, old, new) => Diff::Changed(key.clone(), old.clone(), new.clone()),
+        }
+    }
+}
+
+// Diagnostics of a tree returned by AVL::stats and RedBlack::stats
+#[derive(Debug, Clone, PartialEq)]
+pub struct TreeStats<K, B> {
+    pub len: usize,
+    pub height: usize, // number of edges on the longest path from the root, 0 for empty tree
+    pub min: Option<K>,
+    pub max: Option<K>,
+    pub balance: B, // max absolute balance factor for AVL, black height for red-black tree
+}
+
+// Keys with known next value, used for coalescing consecutive keys into intervals
+pub trait Successor: Sized {
+    // None if there is no greater value
+    fn successor(&self) -> Option<Self>;
+}
+
+macro_rules! impl_successor {
+    ($($integer:ty),*) => {
+        $(impl Successor for $integer {
+            fn successor(&self) -> Option<Self> {
+                self.checked_add(1)
             }
-            Some(node) => {
-                Some(node.max())
+        })*
+    };
+}
+
+impl_successor!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+// Keys with absolute distance, which cannot overflow, used for snapping to the nearest key
+pub trait Distance {
+    type Output: Ord;
+    fn distance(&self, other: &Self) -> Self::Output;
+}
+
+macro_rules! impl_distance {
+    ($($integer:ty => $unsigned:ty),*) => {
+        $(impl Distance for $integer {
+            type Output = $unsigned;
+            fn distance(&self, other: &Self) -> Self::Output {
+                self.abs_diff(*other)
             }
+        })*
+    };
+}
+
+impl_distance!(u8 => u8, u16 => u16, u32 => u32, u64 => u64, u128 => u128, usize => usize);
+impl_distance!(i8 => u8, i16 => u16, i32 => u32, i64 => u64, i128 => u128, isize => usize);
+
+mod sealed {
+    // prevents implementing SortedByKey outside of the crate, order of other iterators is not guaranteed
+    pub trait Sealed {}
+}
+
+// Iterators yielding entries in ascending order of keys, which allows Tree::extend_sorted to skip sorting
+pub trait SortedByKey: Iterator + sealed::Sealed {}
+
+impl<K: Ord, V, I> sealed::Sealed for Iter<'_, K, V, I> {}
+impl<K: Ord, V, I> SortedByKey for Iter<'_, K, V, I> {}
+
+impl<K: Ord, V, I> sealed::Sealed for IntoIter<K, V, I> {}
+impl<K: Ord, V, I> SortedByKey for IntoIter<K, V, I> {}
+
+impl<K, V> sealed::Sealed for btree_map::Iter<'_, K, V> {}
+impl<K, V> SortedByKey for btree_map::Iter<'_, K, V> {}
+
+impl<K, V> sealed::Sealed for btree_map::IntoIter<K, V> {}
+impl<K, V> SortedByKey for btree_map::IntoIter<K, V> {}
+
+// Reason why items are not strictly increasing, index points to the first offending item
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnsortedError {
+    OutOfOrder(usize),
+    DuplicateKey(usize),
+}
+
+impl fmt::Display for UnsortedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnsortedError::OutOfOrder(index) => write!(f, "key at index {} is smaller than the previous one", index),
+            UnsortedError::DuplicateKey(index) => write!(f, "key at index {} equals the previous one", index),
         }
     }
+}
 
-    pub fn next(&self, value: &K) -> Option<(&K, &V)> {
+impl Error for UnsortedError {}
+
+pub struct Tree<K: Ord, V, I> {
+    root: Child<K, V, I>,
+    frozen: bool, // frozen tree cannot be altered by insert and remove
+}
+
+impl<K: Ord, V, I> Default for Tree<K, V, I> {
+    fn default() -> Self {
+        Self::new_empty()
+    }
+}
+
+impl<K: Ord, V, I> Tree<K, V, I> {
+    // shared constructor for all balancing strategies
+    pub(crate) fn new_empty() -> Self {
+        Tree { root: None, frozen: false }
+    }
+
+    pub fn len(&self) -> usize {
+        Node::size_of(&self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// Counts nodes by traversing the whole tree, debugging aid for checking tracked size returned by `len`.
+    /// Mismatch is left to the caller, `assert_invariants` panics on it.
+    pub fn node_count(&self) -> usize {
+        self.iter().count()
+    }
+
+    /// Returns number of edges on the longest path from node with given key down to a leaf, 0 for leaves.
+    pub fn height_of_subtree(&self, key: &K) -> Option<usize> {
+        self.root.as_ref().and_then(|node| node.find_node(key)).map(|node| node.height())
+    }
+
+    /// Returns the deepest recursion of insert and remove, which descend once per level, so it is height in nodes.
+    /// Can be checked before mutating large trees, when stack usage must stay bounded, 0 for empty tree.
+    pub fn max_recursion_depth(&self) -> usize {
+        self.root.as_ref().map_or(0, |node| node.height() + 1)
+    }
+
+    /// Returns whether all inner nodes have two children and all leaves are in the same depth, empty tree is perfect.
+    /// Tree of given height is perfect exactly when it contains the maximal number of nodes for that height.
+    pub fn is_perfect(&self) -> bool {
         match &self.root {
-            None => {
-                None
-            }
-            Some(node) => {
-                node.next(value)
-            }
+            None => true,
+            Some(root) => root.height() < usize::BITS as usize - 1 && root.size == (1 << (root.height() + 1)) - 1,
+        }
+    }
+
+    // moves entries into balanced tree of other strategy, metadata differ in type, so nodes are allocated again
+    fn into_strategy<J: Balancing<K, V>>(self) -> Tree<K, V, J> {
+        let mut tree = Tree::new_empty();
+        tree.frozen = self.frozen;
+        tree.root = Node::build_balanced(self.into_iter().map(|(key, value)| Node::detached(key, value)).collect());
+        tree
+    }
+
+    // common part of stats of all balancing strategies
+    fn stats_with<B>(&self, balance: B) -> TreeStats<K, B>
+    where
+        K: Clone,
+    {
+        TreeStats {
+            len: self.len(),
+            height: self.root.as_ref().map_or(0, |node| node.height()),
+            min: self.min().map(|(key, _)| key.clone()),
+            max: self.max().map(|(key, _)| key.clone()),
+            balance,
+        }
+    }
+
+    /// Returns whether both trees have same keys on same places with same balancing metadata, values are not compared.
+    /// Trees with same entries can differ in structure, e.g. when built by bulk insert and by repeated insert.
+    pub fn structural_eq(&self, other: &Self) -> bool
+    where
+        I: PartialEq,
+    {
+        match (&self.root, &other.root) {
+            (None, None) => true,
+            (Some(root), Some(other)) => root.structural_eq(other),
+            _ => false,
         }
     }
-}
\ No newline at end of file
+
+    /// Transforms keys without rebalancing, the tree keeps its exact shape. Function must be strictly increasing,
+    /// so transformed keys stay in the same order, which is checked in debug builds.
+    pub fn map_keys_monotonic<L: Ord, F: Fn(K) -> L>(self, f: F) -> Tree<L, V, I> {
+        let tree = Tree { root: self.root.map(|root| Box::new(root.map_keys(&f))), frozen: self.frozen };
+        debug_assert!(tree.is_bst(), "key function must be strictly increasing");
+        tree
+    }
+
+    // metadata of node with given key
+    fn metadata_of(&self, key: &K) -> Option<&I> {
+        self.root.as_ref().and_then(|node| node.find_node(key)).map(|node| &node.metadata)
+    }
+
+    /// Estimates heap bytes used by nodes, allocator bookkeeping of each box is only guessed.
+    /// Heap memory owned by keys and values themselves is not included.
+    pub fn memory_bytes(&self) -> usize {
+        self.len() * (mem::size_of::<Node<K, V, I>>() + ALLOCATOR_OVERHEAD)
+    }
+
+    fn find_mut<Q: Ord + ?Sized>(&mut self, key: &Q) -> Option<(&K, &mut V)>
+    where
+        K: Borrow<Q>,
+    {
+        self.root.as_mut().and_then(|node| node.find_mut(key))
+    }
+
+    /// Forbids insertion and removal until `thaw` is called, altering frozen tree panics.
+    pub fn freeze(&mut self) {
+        self.frozen = true;
+    }
+
+    pub fn thaw(&mut self) {
+        self.frozen = false;
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    fn assert_not_frozen(&self) {
+        assert!(!self.frozen, "frozen tree cannot be altered, thaw it first");
+    }
+
+    // number of keys, which are smaller than given bound and therefore do not satisfy it
+    fn rank_of_lower_bound(&self, bound: Bound<&K>) -> usize {
+        match bound {
+            Bound::Included(key) => self.rank(key),
+            Bound::Excluded(key) => self.rank(key) + self.find(key).map_or(0, |_| 1),
+            Bound::Unbounded => 0,
+        }
+    }
+
+    // number of keys satisfying given upper bound
+    fn rank_of_upper_bound(&self, bound: Bound<&K>) -> usize {
+        match bound {
+            Bound::Included(key) => self.rank(key) + self.find(key).map_or(0, |_| 1),
+            Bound::Excluded(key) => self.rank(key),
+            Bound::Unbounded => Node::size_of(&self.root),
+        }
+    }
+
+    // number of keys in given range
+    fn count_in_range<R: RangeBounds<K>>(&self, range: &R) -> usize {
+        self.rank_of_upper_bound(range.end_bound())
+            .saturating_sub(self.rank_of_lower_bound(range.start_bound()))
+    }
+
+    fn rank(&self, key: &K) -> usize {
+        self.root.as_ref().map_or(0, |node| node.rank(key))
+    }
+
+    /// Returns keys at ranks len * i / n for i in 1..n, which split keys into n parts of nearly same size.
+    pub fn sample_keys(&self, n: usize) -> Vec<&K> {
+        let Some(root) = self.root.as_ref() else {
+            return Vec::new();
+        };
+        (1..n).filter_map(|i| root.select(self.len() * i / n)).map(|node| &node.key).collect()
+    }
+
+    /// Returns number of keys smaller than strict successor of given key, None if there is no successor.
+    pub fn rank_of_successor(&self, key: &K) -> Option<usize> {
+        let rank = self.rank_of_upper_bound(Bound::Included(key));
+        (rank < self.len()).then_some(rank)
+    }
+
+    /// Returns number of keys smaller than strict predecessor of given key, None if there is no predecessor.
+    pub fn rank_of_predecessor(&self, key: &K) -> Option<usize> {
+        self.rank(key).checked_sub(1)
+    }
+
+    /// Returns number of keys strictly between given key and its successor, None if key is not in the tree
+    /// or it is the largest one, so it is 0 for every other key.
+    pub fn gap_rank(&self, key: &K) -> Option<usize> {
+        self.find(key)?;
+        let successor = self.rank_of_successor(key)?;
+        Some(successor - self.rank(key) - 1)
+    }
+
+    fn select_mut(&mut self, rank: usize) -> Option<(&K, &mut V)> {
+        self.root.as_mut().and_then(|node| node.select_mut(rank))
+    }
+
+    pub fn find(&self, value: &K) -> Option<&V> {
+        match &self.root {
+            None => {
+                None
+            }
+            Some(node) => {
+                node.find(value)
+            }
+        }
+    }
+
+    pub fn min(&self) -> Option<(&K, &V)> {
+        self.root.as_ref().map(|node| node.min())
+    }
+
+    pub fn max(&self) -> Option<(&K, &V)> {
+        self.root.as_ref().map(|node| node.max())
+    }
+
+    /// Finds values of sorted keys by one inorder sweep in O(n + m) instead of m searches from the root.
+    /// Unsorted keys are searched one by one from the root, so the result is the same only slower.
+    pub fn find_batch<'a>(&'a self, keys: &[K]) -> Vec<Option<&'a V>> {
+        if !keys.windows(2).all(|pair| pair[0] <= pair[1]) {
+            return keys.iter().map(|key| self.find(key)).collect();
+        }
+        let mut entries = self.iter().peekable();
+        keys.iter().map(|key| {
+            while entries.next_if(|(entry_key, _)| *entry_key < key).is_some() {}
+            entries.peek().filter(|(entry_key, _)| *entry_key == key).map(|(_, value)| *value)
+        }).collect()
+    }
+
+    /// Returns entry with given key and true, or the nearer of its floor and ceiling and false if key is missing,
+    /// ties are resolved by the floor. None only for empty tree.
+    pub fn find_or_nearest(&self, key: &K) -> Option<(&K, &V, bool)>
+    where
+        K: Distance,
+    {
+        let mut root = self.root.as_deref();
+        // last nodes where search turned right and left
+        let mut floor: Option<&Node<K, V, I>> = None;
+        let mut ceiling: Option<&Node<K, V, I>> = None;
+        while let Some(node) = root {
+            match key.cmp(&node.key) {
+                Ordering::Less => {
+                    ceiling = Some(node);
+                    root = node.left_child.as_deref();
+                }
+                Ordering::Greater => {
+                    floor = Some(node);
+                    root = node.right_child.as_deref();
+                }
+                Ordering::Equal => {
+                    return Some((&node.key, &node.value, true));
+                }
+            }
+        }
+        let nearest = match (floor, ceiling) {
+            (Some(floor), Some(ceiling)) => {
+                if key.distance(&floor.key) <= key.distance(&ceiling.key) { floor } else { ceiling }
+            }
+            (floor, ceiling) => floor.or(ceiling)?,
+        };
+        Some((&nearest.key, &nearest.value, false))
+    }
+
+    /// Returns the smallest and the largest key, None for empty tree.
+    pub fn key_range(&self) -> Option<(&K, &K)> {
+        self.root.as_ref().map(|node| (node.min().0, node.max().0))
+    }
+
+    pub fn next(&self, value: &K) -> Option<(&K, &V)> {
+        match &self.root {
+            None => {
+                None
+            }
+            Some(node) => {
+                node.next(value)
+            }
+        }
+    }
+
+    /// Returns entry with the smallest key greater than or equal to given key.
+    pub fn ceiling(&self, key: &K) -> Option<(&K, &V)> {
+        self.neighbor(|node| node.ceiling(Bound::Included(key)))
+    }
+
+    /// Returns entry with the largest key less than or equal to given key.
+    pub fn floor(&self, key: &K) -> Option<(&K, &V)> {
+        self.neighbor(|node| node.floor(Bound::Included(key)))
+    }
+
+    /// Returns entry with the smallest key strictly greater than given key, unlike `ceiling` never returns given key.
+    pub fn first_after(&self, key: &K) -> Option<(&K, &V)> {
+        self.neighbor(|node| node.ceiling(Bound::Excluded(key)))
+    }
+
+    /// Returns entry with the largest key strictly less than given key, unlike `floor` never returns given key.
+    pub fn last_before(&self, key: &K) -> Option<(&K, &V)> {
+        self.neighbor(|node| node.floor(Bound::Excluded(key)))
+    }
+
+    /// Returns strict predecessor and strict successor of given key in one descent, key does not need to be present.
+    pub fn neighbors(&self, key: &K) -> Neighbors<'_, K, V> {
+        self.root.as_ref().map_or((None, None), |node| node.neighbors(key))
+    }
+
+    fn neighbor<'a, F>(&'a self, search: F) -> Option<EntryRef<'a, K, V>>
+    where
+        F: FnOnce(&'a Node<K, V, I>) -> Option<&'a Node<K, V, I>>,
+    {
+        self.root.as_deref().and_then(search).map(|node| (&node.key, &node.value))
+    }
+
+    /// Returns mutable values of two different keys at once, panics if keys are equal.
+    pub fn get2_mut(&mut self, first: &K, second: &K) -> (Option<&mut V>, Option<&mut V>) {
+        assert!(first != second, "keys must be different to borrow both values mutably");
+        match self.root.as_mut() {
+            None => (None, None),
+            Some(node) => node.find_two_mut(first, second),
+        }
+    }
+
+    /// Returns keys from the root down to the node with given key (inclusive), None if key is not in the tree.
+    pub fn path_to(&self, key: &K) -> Option<Vec<&K>> {
+        self.root.as_ref().and_then(|node| node.path_to(key))
+    }
+
+    /// Returns values of nodes visited by search for given key from the root down, also when the key is missing.
+    pub fn search_path_values(&self, key: &K) -> Vec<&V> {
+        self.root.as_ref().map_or_else(Vec::new, |node| node.search_path_values(key))
+    }
+
+    /// Returns number of edges between the root and node with given key.
+    pub fn depth_of(&self, key: &K) -> Option<usize> {
+        self.path_to(key).map(|path| path.len() - 1)
+    }
+
+    /// Returns entries grouped by depth, the first group contains only the root, groups are ordered by keys.
+    pub fn iter_levels(&self) -> Vec<Vec<EntryRef<'_, K, V>>> {
+        let mut levels = Vec::new();
+        let mut level: Vec<&Node<K, V, I>> = self.root.as_deref().into_iter().collect();
+        while !level.is_empty() {
+            levels.push(level.iter().map(|node| (&node.key, &node.value)).collect());
+            level = level.iter()
+                .flat_map(|node| [node.left_child.as_deref(), node.right_child.as_deref()])
+                .flatten()
+                .collect();
+        }
+        levels
+    }
+
+    /// Folds entries of each depth separately starting from clone of `init`, the first value belongs to the root.
+    pub fn fold_levels<B: Clone, F: Fn(B, &K, &V) -> B>(&self, init: B, f: F) -> Vec<B> {
+        self.iter_levels()
+            .into_iter()
+            .map(|level| level.into_iter().fold(init.clone(), |acc, (key, value)| f(acc, key, value)))
+            .collect()
+    }
+
+    /// Returns all keys in inorder succession for assertions in tests.
+    #[cfg(test)]
+    pub(crate) fn inorder_keys(&self) -> Vec<&K> {
+        self.iter().map(|(key, _)| key).collect()
+    }
+
+    /// Iterates over (key, value) in ascending order of keys.
+    pub fn iter(&self) -> Iter<'_, K, V, I> {
+        Iter::new(self.root.as_deref())
+    }
+
+    /// Calls function on entries in ascending order of keys and stops at the first error, which is returned.
+    pub fn try_for_each<E, F: FnMut(&K, &V) -> Result<(), E>>(&self, mut f: F) -> Result<(), E> {
+        self.iter().try_for_each(|(key, value)| f(key, value))
+    }
+
+    /// Appends clones of all entries in ascending order of keys to given vector, which allows reusing its allocation.
+    pub fn collect_into(&self, out: &mut Vec<(K, V)>)
+    where
+        K: Clone,
+        V: Clone,
+    {
+        out.reserve(self.len());
+        out.extend(self.iter().map(|(key, value)| (key.clone(), value.clone())));
+    }
+
+    /// Moves all entries into vector sorted by keys, no key or value is cloned.
+    pub fn into_sorted_vec(self) -> Vec<(K, V)> {
+        self.into_iter().collect()
+    }
+
+    /// Iterates over (depth, key, value) in ascending order of keys, the root has depth 0.
+    pub fn iter_with_depth(&self) -> DepthIter<'_, K, V, I> {
+        DepthIter::new(self.root.as_deref())
+    }
+
+    /// Iterates in ascending order over groups of `size` entries, the last group may be smaller. Panics if `size` is 0.
+    pub fn iter_chunks(&self, size: usize) -> impl Iterator<Item=Vec<EntryRef<'_, K, V>>> {
+        assert!(size > 0, "chunk size must be positive");
+        let mut iter = self.iter();
+        from_fn(move || {
+            let chunk: Vec<EntryRef<'_, K, V>> = iter.by_ref().take(size).collect();
+            (!chunk.is_empty()).then_some(chunk)
+        })
+    }
+
+    /// Iterates in ascending order starting from the smallest key greater than or equal to `key`.
+    pub fn iter_from(&self, key: &K) -> Iter<'_, K, V, I> {
+        Iter::from_lower_bound(self.root.as_deref(), Bound::Included(key))
+    }
+
+    /// Returns the smallest and the largest entry in given range without iterating over the range.
+    pub fn range_endpoints<R: RangeBounds<K>>(&self, range: R) -> Option<(EntryRef<'_, K, V>, EntryRef<'_, K, V>)> {
+        let root = self.root.as_ref()?;
+        let first = root.ceiling(range.start_bound())?;
+        let last = root.floor(range.end_bound())?;
+        // both bounds may be satisfied by different keys while no key satisfies both
+        if first.key <= last.key {
+            Some(((&first.key, &first.value), (&last.key, &last.value)))
+        } else {
+            None
+        }
+    }
+
+    /// Iterates over entries with keys in given range in ascending order.
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> Take<Iter<'_, K, V, I>> {
+        let count = self.count_in_range(&range);
+        Iter::from_lower_bound(self.root.as_deref(), range.start_bound()).take(count)
+    }
+
+    /// Returns ranks of entries with keys in given range, empty range starts at rank of the first greater key.
+    pub fn rank_range<R: RangeBounds<K>>(&self, range: R) -> Range<usize> {
+        let start = self.rank_of_lower_bound(range.start_bound());
+        start..self.rank_of_upper_bound(range.end_bound()).max(start)
+    }
+
+    /// Returns number of keys in `[lo, hi)` in O(log n), 0 if `lo` is greater than `hi`.
+    pub fn count_between(&self, lo: &K, hi: &K) -> usize {
+        self.rank(hi).saturating_sub(self.rank(lo))
+    }
+
+    /// Collects entries with keys in given range in ascending order.
+    pub fn find_all_in<R: RangeBounds<K>>(&self, range: R) -> Vec<EntryRef<'_, K, V>> {
+        self.range(range).collect()
+    }
+
+    /// Returns at most `n` entries with the smallest keys in given range in ascending order.
+    pub fn first_n_in<R: RangeBounds<K>>(&self, range: R, n: usize) -> Vec<EntryRef<'_, K, V>> {
+        self.range(range).take(n).collect()
+    }
+
+    /// Returns at most `n` entries with the largest keys in given range in ascending order.
+    /// The first returned entry is found by rank, so entries before it are not visited.
+    pub fn last_n_in<R: RangeBounds<K>>(&self, range: R, n: usize) -> Vec<EntryRef<'_, K, V>> {
+        let Some(root) = self.root.as_deref() else {
+            return Vec::new();
+        };
+        let end = self.rank_of_upper_bound(range.end_bound());
+        let start = self.rank_of_lower_bound(range.start_bound()).max(end.saturating_sub(n));
+        match root.select(start) {
+            Some(first) if start < end => {
+                Iter::from_lower_bound(Some(root), Bound::Included(&first.key)).take(end - start).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Folds entries with keys in given range in ascending order, entries before the range are skipped by descent.
+    pub fn reduce_range<B, R: RangeBounds<K>, F: FnMut(B, &K, &V) -> B>(&self, range: R, init: B, mut f: F) -> B {
+        self.range(range).fold(init, |accumulator, (key, value)| f(accumulator, key, value))
+    }
+
+    /// Iterates over entries with keys in given range in ascending order with mutable values.
+    pub fn range_mut<R: RangeBounds<K>>(&mut self, range: R) -> RangeMut<'_, K, V, I> {
+        let count = self.count_in_range(&range);
+        RangeMut::new(self.root.as_deref_mut(), range.start_bound(), count)
+    }
+
+    /// Overwrites values of keys already in the tree, updates of missing keys are skipped, so nothing is inserted.
+    pub fn batch_apply<T: IntoIterator<Item=(K, V)>>(&mut self, updates: T) {
+        self.assert_not_frozen();
+        for (key, value) in updates {
+            if let Some((_, old_value)) = self.find_mut(&key) {
+                *old_value = value;
+            }
+        }
+    }
+
+    /// Applies function to entries with keys in given range in ascending order of keys.
+    pub fn apply_range<R: RangeBounds<K>, F: FnMut(&K, &mut V)>(&mut self, range: R, mut f: F) {
+        self.range_mut(range).for_each(|(key, value)| f(key, value));
+    }
+
+    /// Iterates over mutable values of keys in given range in ascending order of keys.
+    pub fn range_values_mut<R: RangeBounds<K>>(&mut self, range: R) -> impl Iterator<Item=&mut V> {
+        self.range_mut(range).map(|(_, value)| value)
+    }
+
+    /// Returns whether keys are strictly increasing in inorder succession, which is the search tree property.
+    /// Only ordering is checked, balancing metadata are ignored, so it applies to every balancing strategy.
+    pub fn is_bst(&self) -> bool {
+        self.iter().zip(self.iter().skip(1)).all(|((key, _), (next, _))| key < next)
+    }
+
+    /// Returns whether some key equals its predecessor in inorder succession, which cannot happen in trees built by safe
+    /// operations of this crate, checks the whole tree in ascending order. Keys out of order are not reported
+    /// and equal keys separated by them are missed, `is_bst` checks the order.
+    pub fn has_duplicate_keys(&self) -> bool {
+        self.iter().zip(self.iter().skip(1)).any(|((key, _), (next, _))| key == next)
+    }
+
+    /// Returns whether all given keys are in the tree, stops at the first missing one. True for no keys.
+    pub fn contains_all(&self, keys: &[K]) -> bool {
+        keys.iter().all(|key| self.find(key).is_some())
+    }
+
+    /// Returns whether at least one of given keys is in the tree, stops at the first present one. False for no keys.
+    pub fn contains_any(&self, keys: &[K]) -> bool {
+        keys.iter().any(|key| self.find(key).is_some())
+    }
+
+    /// Returns whether some key maps to given value, entries are scanned in ascending order until one does.
+    pub fn contains_value(&self, value: &V) -> bool
+    where
+        V: PartialEq,
+    {
+        self.iter().any(|(_, other)| other == value)
+    }
+
+    /// Returns entry with the smallest key whose value is at least given one, entries are scanned in ascending order.
+    pub fn first_key_with_value_at_least(&self, value: &V) -> Option<EntryRef<'_, K, V>>
+    where
+        V: Ord,
+    {
+        self.iter().find(|(_, other)| *other >= value)
+    }
+
+    /// Returns the smallest key mapping to given value, entries are scanned in ascending order.
+    pub fn key_of_value(&self, value: &V) -> Option<&K>
+    where
+        V: PartialEq,
+    {
+        self.iter().find(|(_, other)| *other == value).map(|(key, _)| key)
+    }
+
+    /// Coalesces runs of consecutive keys with equal values into (start, end, value) intervals with inclusive ends.
+    pub fn to_intervals(&self) -> Vec<(K, K, &V)>
+    where
+        K: Successor + Clone,
+        V: PartialEq,
+    {
+        let mut intervals: Vec<(K, K, &V)> = Vec::new();
+        for (key, value) in self.iter() {
+            match intervals.last_mut() {
+                Some((_, end, last_value)) if *last_value == value && end.successor().as_ref() == Some(key) => {
+                    *end = key.clone();
+                }
+                _ => {
+                    intervals.push((key.clone(), key.clone(), value));
+                }
+            }
+        }
+        intervals
+    }
+
+    /// Returns keys of given range missing in the tree in ascending order. Unbounded side of the range is limited
+    /// by the outermost key of the tree in the range, so only gaps between keys are reported there.
+    pub fn missing_in<R: RangeBounds<K>>(&self, range: R) -> Vec<K>
+    where
+        K: Successor + Clone,
+    {
+        let mut missing = Vec::new();
+        // the smallest key in range, which was not checked yet, None once it would be greater than any key
+        let mut expected = match range.start_bound() {
+            Bound::Included(start) => Some(start.clone()),
+            Bound::Excluded(start) => start.successor(),
+            Bound::Unbounded => self.range(..).next().map(|(key, _)| key.clone()),
+        };
+        for (key, _) in self.range((range.start_bound(), range.end_bound())) {
+            while let Some(gap) = expected.take_if(|gap| &*gap < key) {
+                expected = gap.successor();
+                missing.push(gap);
+            }
+            expected = key.successor();
+        }
+        if !matches!(range.end_bound(), Bound::Unbounded) {
+            while let Some(gap) = expected.take_if(|gap| satisfies_upper_bound(gap, range.end_bound())) {
+                expected = gap.successor();
+                missing.push(gap);
+            }
+        }
+        missing
+    }
+
+    /// Returns number of entries whose value is shared with at least one other entry.
+    pub fn duplicate_value_count(&self) -> usize
+    where
+        V: Ord,
+    {
+        let mut values: Vec<&V> = self.iter().map(|(_, value)| value).collect();
+        values.sort();
+        values.chunk_by(|a, b| a == b).filter(|run| run.len() > 1).map(|run| run.len()).sum()
+    }
+
+    /// Counts entries whose value satisfies predicate, all entries are visited.
+    pub fn count_values<F: FnMut(&V) -> bool>(&self, mut predicate: F) -> usize {
+        self.iter().filter(|(_, value)| predicate(value)).count()
+    }
+
+    /// Returns whether any entry satisfies predicate, entries are visited in ascending order until one does.
+    pub fn any<F: FnMut(&K, &V) -> bool>(&self, mut predicate: F) -> bool {
+        self.iter().any(|(key, value)| predicate(key, value))
+    }
+
+    /// Returns whether all entries satisfy predicate, entries are visited in ascending order until one does not.
+    pub fn all<F: FnMut(&K, &V) -> bool>(&self, mut predicate: F) -> bool {
+        self.iter().all(|(key, value)| predicate(key, value))
+    }
+
+    /// Iterates in ascending order over keys present in both trees together with values from both of them.
+    pub fn zip_matched<'a, W, J>(&'a self, other: &'a Tree<K, W, J>) -> impl Iterator<Item=(&'a K, &'a V, &'a W)> {
+        let mut left = self.iter().peekable();
+        let mut right = other.iter().peekable();
+        // merge both sorted sequences, skipping keys present only in one of them
+        from_fn(move || loop {
+            match left.peek()?.0.cmp(right.peek()?.0) {
+                Ordering::Less => {
+                    left.next();
+                }
+                Ordering::Greater => {
+                    right.next();
+                }
+                Ordering::Equal => {
+                    let (key, value) = left.next()?;
+                    let (_, other_value) = right.next()?;
+                    return Some((key, value, other_value));
+                }
+            }
+        })
+    }
+
+    /// Iterates in ascending order of keys over changes turning this tree into other one, keys present only
+    /// in other tree are added, keys present only in this tree are removed and keys with different values are changed.
+    pub fn diff<'a>(&'a self, other: &'a Self) -> impl Iterator<Item=Diff<&'a K, &'a V>>
+    where
+        V: PartialEq,
+    {
+        let mut own = self.iter().peekable();
+        let mut others = other.iter().peekable();
+        from_fn(move || loop {
+            let ordering = match (own.peek(), others.peek()) {
+                (None, None) => return None,
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (Some((key, _)), Some((other_key, _))) => key.cmp(other_key),
+            };
+            match ordering {
+                Ordering::Less => {
+                    let (key, value) = own.next()?;
+                    return Some(Diff::Removed(key, value));
+                }
+                Ordering::Greater => {
+                    let (key, value) = others.next()?;
+                    return Some(Diff::Added(key, value));
+                }
+                Ordering::Equal => {
+                    let (key, value) = own.next()?;
+                    let (_, other_value) = others.next()?;
+                    if value != other_value {
+                        return Some(Diff::Changed(key, value, other_value));
+                    }
+                }
+            }
+        })
+    }
+
+    /// Merges clones of entries with already sorted stream into one sorted sequence without building another tree.
+    /// Entry of the stream wins when both contain the same key.
+    pub fn merge_with_iter<'a, T: IntoIterator<Item=(K, V)>>(&'a self, sorted: T) -> impl Iterator<Item=(K, V)> + 'a
+    where
+        K: Clone,
+        V: Clone,
+        T::IntoIter: 'a,
+    {
+        let mut own = self.iter().peekable();
+        let mut sorted = sorted.into_iter().peekable();
+        from_fn(move || {
+            let ordering = match (own.peek(), sorted.peek()) {
+                (None, None) => return None,
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (Some((key, _)), Some((other, _))) => (*key).cmp(other),
+            };
+            if ordering == Ordering::Less {
+                return own.next().map(|(key, value)| (key.clone(), value.clone()));
+            }
+            if ordering == Ordering::Equal {
+                own.next();
+            }
+            sorted.next()
+        })
+    }
+
+    /// Returns at most `limit` entries with keys strictly greater than `after`, or from the smallest key if `after` is None.
+    /// Panics if `limit` is 0, because such page could not tell where the next one starts.
+    pub fn page(&self, after: Option<&K>, limit: usize) -> Page<'_, K, V> {
+        assert!(limit > 0, "page limit must be positive");
+        let bound = after.map_or(Bound::Unbounded, Bound::Excluded);
+        let mut iter = Iter::from_lower_bound(self.root.as_deref(), bound);
+        let entries: Vec<(&K, &V)> = iter.by_ref().take(limit).collect();
+        let next_after = match (entries.last(), iter.next()) {
+            (Some((key, _)), Some(_)) => Some(*key),
+            _ => None,
+        };
+
+        Page { entries, next_after }
+    }
+
+    /// Returns entry with the smallest value, ties are resolved by the smallest key.
+    pub fn min_by_value(&self) -> Option<(&K, &V)>
+    where
+        V: Ord,
+    {
+        // min_by_key returns first minimum, which is the one with the smallest key
+        self.iter().min_by_key(|(_, value)| *value)
+    }
+
+    /// Returns entry with the largest value, ties are resolved by the smallest key.
+    pub fn max_by_value(&self) -> Option<(&K, &V)>
+    where
+        V: Ord,
+    {
+        // max_by_key would return last maximum, so first one must be kept explicitly
+        self.iter().fold(None, |max, entry| match max {
+            Some(max) if entry.1 <= max.1 => Some(max),
+            _ => Some(entry),
+        })
+    }
+}
+
+impl<K: Ord, V: Copy, I> Tree<K, V, I> {
+    /// Sums all values in the tree.
+    pub fn values_sum(&self) -> V
+    where
+        V: Sum,
+    {
+        self.iter().map(|(_, value)| *value).sum()
+    }
+
+    /// Returns the largest value in the tree.
+    pub fn values_max(&self) -> Option<V>
+    where
+        V: Ord,
+    {
+        self.iter().map(|(_, value)| *value).max()
+    }
+
+    /// Returns the smallest value in the tree.
+    pub fn values_min(&self) -> Option<V>
+    where
+        V: Ord,
+    {
+        self.iter().map(|(_, value)| *value).min()
+    }
+
+    /// Returns entry whose value is closest to target, scans all entries, ties are resolved by the smaller key.
+    pub fn nearest_by_value(&self, target: &V) -> Option<(&K, &V)>
+    where
+        V: Ord + Sub<Output=V>,
+    {
+        let target = *target;
+        let distance = |value: V| if value > target { value - target } else { target - value };
+        self.iter().min_by_key(|(_, value)| distance(**value))
+    }
+}
+
+impl<K: Ord, V, I> IntoIterator for Tree<K, V, I> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V, I>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter::new(self.root)
+    }
+}
+
+impl<'a, K: Ord, V, I> IntoIterator for &'a Tree<K, V, I> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V, I>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K: Ord, V, I: Balancing<K, V>> Tree<K, V, I> {
+    /// Returns whether keys are ordered, stored subtree sizes are correct and rules of balancing strategy hold.
+    pub fn is_valid(&self) -> bool {
+        self.violation().is_none()
+    }
+
+    /// Panics with description of the first broken property, gives precise diagnostics in tests compared to is_valid.
+    #[cfg(debug_assertions)]
+    pub fn assert_invariants(&self) {
+        if let Some(violation) = self.violation() {
+            panic!("tree invariant violated: {}", violation);
+        }
+    }
+
+    fn violation(&self) -> Option<String> {
+        if !self.is_bst() {
+            return Some("BST order, keys must be strictly increasing in inorder succession".to_string());
+        }
+        if Node::checked_size(&self.root).is_none() {
+            return Some("size of some node does not match number of nodes in its subtree".to_string());
+        }
+        I::check_invariants(self).err()
+    }
+
+    /// Returns entry for given key for in-place manipulation.
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, I> {
+        if self.find_mut(&key).is_some() {
+            let (key, value) = self.find_mut(&key).unwrap();
+            Entry::Occupied(entry::OccupiedEntry { key, value })
+        } else {
+            Entry::Vacant(entry::VacantEntry { tree: self, key })
+        }
+    }
+
+    /// Inserts value only if key is not in the tree yet, otherwise returns given key and value back.
+    pub fn try_insert(&mut self, key: K, value: V) -> Result<&mut V, (K, V)> {
+        if self.find(&key).is_some() {
+            Err((key, value))
+        } else {
+            Ok(entry::VacantEntry { tree: self, key }.insert(value))
+        }
+    }
+
+    /// Inserts entry only if `min <= key <= max`, otherwise returns given key and value back.
+    /// Returns previous value of the key, which itself is kept when it was already in the tree.
+    pub fn bounded_insert(&mut self, key: K, value: V, min: &K, max: &K) -> Result<Option<V>, (K, V)> {
+        self.assert_not_frozen();
+        if key < *min || key > *max {
+            return Err((key, value));
+        }
+        match self.find_mut(&key) {
+            Some((_, old_value)) => Ok(Some(mem::replace(old_value, value))),
+            None => {
+                I::insert(self, key, value);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Inserts entry and adds number of rotations performed by balancing to `rotations`, double rotation counts twice.
+    /// Requires `rotation-stats` feature.
+    #[cfg(any(test, feature = "rotation-stats"))]
+    pub fn insert_counting_rotations(&mut self, key: K, value: V, rotations: &mut u64) {
+        counting_rotations(rotations, || I::insert(self, key, value))
+    }
+
+    /// Removes entry and adds number of rotations performed by balancing to `rotations`, double rotation counts twice.
+    /// Requires `rotation-stats` feature.
+    #[cfg(any(test, feature = "rotation-stats"))]
+    pub fn remove_counting_rotations(&mut self, key: &K, rotations: &mut u64) -> Option<(K, V)> {
+        counting_rotations(rotations, || I::remove(self, key))
+    }
+
+    /// Adds one to value of given key, missing key is inserted with value one.
+    pub fn increment(&mut self, key: K)
+    where
+        V: From<u8> + AddAssign,
+    {
+        self.assert_not_frozen();
+        match self.find_mut(&key) {
+            Some((_, value)) => *value += V::from(1),
+            None => I::insert(self, key, V::from(1)),
+        }
+    }
+
+    /// Subtracts one from value of given key and removes the entry once it reaches zero.
+    /// Missing key is inserted with minus one, so values must be signed.
+    pub fn decrement(&mut self, key: K)
+    where
+        V: From<u8> + SubAssign + Neg<Output=V> + PartialEq,
+    {
+        self.assert_not_frozen();
+        match self.find_mut(&key) {
+            Some((_, value)) => {
+                *value -= V::from(1);
+                if *value == V::from(0) {
+                    I::remove(self, &key);
+                }
+            }
+            None => I::insert(self, key, -V::from(1)),
+        }
+    }
+
+    /// Removes every key present in other tree. Shared keys are found by one pass over both trees in ascending order,
+    /// then removed one by one.
+    pub fn difference_update(&mut self, other: &Self) {
+        let mut shared = Vec::new();
+        let mut own = self.iter().peekable();
+        for (key, _) in other.iter() {
+            while own.next_if(|(own_key, _)| *own_key < key).is_some() {}
+            if own.next_if(|(own_key, _)| *own_key == key).is_some() {
+                shared.push(key);
+            }
+        }
+        for key in shared {
+            I::remove(self, key);
+        }
+    }
+
+    /// Applies changes computed by diff, added and changed keys get new values and removed keys are removed.
+    pub fn apply_diff<T: IntoIterator<Item=Diff<K, V>>>(&mut self, diff: T) {
+        for change in diff {
+            match change {
+                Diff::Added(key, value) | Diff::Changed(key, _, value) => I::insert(self, key, value),
+                Diff::Removed(key, _) => {
+                    I::remove(self, &key);
+                }
+            }
+        }
+    }
+
+    /// Moves entry with the smallest key into other tree, returns false if this tree is empty.
+    pub fn move_min_to(&mut self, destination: &mut Self) -> bool {
+        match self.pop_first() {
+            Some((key, value)) => {
+                I::insert(destination, key, value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Inserts each item, or combines its value into the existing value if key is already in the tree.
+    pub fn upsert_batch<T: IntoIterator<Item=(K, V)>, F: FnMut(&mut V, V)>(&mut self, items: T, mut combine: F) {
+        for (key, value) in items {
+            match self.entry(key) {
+                Entry::Occupied(mut entry) => combine(entry.get_mut(), value),
+                Entry::Vacant(entry) => {
+                    entry.insert(value);
+                }
+            }
+        }
+    }
+
+    /// Removes entry with the smallest key.
+    pub fn pop_first(&mut self) -> Option<(K, V)> {
+        I::pop_first(self)
+    }
+
+    /// Removes entry with the largest key.
+    pub fn pop_last(&mut self) -> Option<(K, V)> {
+        I::pop_last(self)
+    }
+
+    /// Returns value of given key, inserting `default` if key is missing, flag is true if insertion happened.
+    pub fn get_or_insert(&mut self, key: K, default: V) -> (&mut V, bool) {
+        match self.entry(key) {
+            Entry::Occupied(entry) => (entry.into_mut(), false),
+            Entry::Vacant(entry) => (entry.insert(default), true),
+        }
+    }
+
+    /// Looks up entry by borrowed key, owned key is needed only when inserting into vacant entry.
+    pub fn entry_ref<'a, Q: Ord + ?Sized>(&'a mut self, key: &'a Q) -> RawEntry<'a, K, V, I, Q>
+    where
+        K: Borrow<Q>,
+    {
+        // borrow checker does not allow returning borrow from first lookup conditionally, so node is searched twice
+        if self.find_mut(key).is_some() {
+            let (key, value) = self.find_mut(key).unwrap();
+            RawEntry::Occupied(entry::OccupiedEntry { key, value })
+        } else {
+            RawEntry::Vacant(entry::RawVacantEntry { tree: self, key })
+        }
+    }
+
+    /// Rebuilds the tree to minimal height, nodes are only relinked, so nothing is allocated or freed except O(n) pointers.
+    pub fn shrink_to_balanced(&mut self) {
+        self.assert_not_frozen();
+        let mut nodes = Vec::with_capacity(self.len());
+        if let Some(root) = self.root.take() {
+            Node::flatten(root, &mut nodes);
+        }
+        self.root = Node::build_balanced(nodes);
+    }
+
+    /// Removes the largest keys until at most `max_len` entries remain.
+    /// Takes O(n) if anything is removed, because the tree is rebuilt by `split_at_rank`.
+    pub fn trim_to_smallest(&mut self, max_len: usize) {
+        if self.len() > max_len {
+            self.split_at_rank(max_len);
+        }
+    }
+
+    /// Removes the smallest keys until at most `max_len` entries remain.
+    /// Takes O(n) if anything is removed, because the tree is rebuilt by `split_at_rank`.
+    pub fn trim_to_largest(&mut self, max_len: usize) {
+        if self.len() > max_len {
+            *self = self.split_at_rank(self.len() - max_len);
+        }
+    }
+
+    /// Moves all entries of other tree into this one, values of keys present in both trees are combined by `resolve`,
+    /// which gets value of this tree first. The tree is rebuilt from nodes of both trees in O(n + m).
+    pub fn merge_with<F: FnMut(&K, V, V) -> V>(&mut self, other: Self, mut resolve: F) {
+        self.assert_not_frozen();
+        let mut own = Vec::with_capacity(self.len());
+        if let Some(root) = self.root.take() {
+            Node::flatten(root, &mut own);
+        }
+        let mut others = Vec::with_capacity(other.len());
+        if let Some(root) = other.root {
+            Node::flatten(root, &mut others);
+        }
+
+        let mut nodes = Vec::with_capacity(own.len() + others.len());
+        let mut own = own.into_iter().peekable();
+        for node in others {
+            while let Some(smaller) = own.next_if(|own_node| own_node.key < node.key) {
+                nodes.push(smaller);
+            }
+            match own.next_if(|own_node| own_node.key == node.key) {
+                Some(own_node) => {
+                    let Node { key, value, .. } = *own_node;
+                    let value = resolve(&key, value, node.value);
+                    nodes.push(Node::detached(key, value));
+                }
+                None => {
+                    nodes.push(node);
+                }
+            }
+        }
+        nodes.extend(own);
+
+        self.root = Node::build_balanced(nodes);
+    }
+
+    /// Empties the tree, entries are yielded level by level of minimal height tree built from them.
+    /// Inserting entries in this order into empty AVL tree reproduces that tree without any rotation.
+    pub fn drain_balanced_order(&mut self) -> impl Iterator<Item=(K, V)> {
+        self.assert_not_frozen();
+        let mut nodes = Vec::with_capacity(self.len());
+        if let Some(root) = self.root.take() {
+            Node::flatten(root, &mut nodes);
+        }
+        let mut entries: Vec<Option<(K, V)>> = nodes.into_iter().map(|node| {
+            let Node { key, value, .. } = *node;
+            Some((key, value))
+        }).collect();
+
+        // ranges of sorted entries forming subtrees, split same way as in build_balanced
+        let mut subtrees: VecDeque<_> = (!entries.is_empty()).then_some((0, entries.len())).into_iter().collect();
+        from_fn(move || {
+            let (start, count) = subtrees.pop_front()?;
+            let left_count = (count - 1) / 2;
+            if left_count > 0 {
+                subtrees.push_back((start, left_count));
+            }
+            if count - left_count - 1 > 0 {
+                subtrees.push_back((start + left_count + 1, count - left_count - 1));
+            }
+            entries[start + left_count].take()
+        })
+    }
+
+    /// Removes entries whose value equals value of the preceding kept entry, so only the first entry of each run
+    /// of equal values in ascending order of keys is kept.
+    pub fn dedup_values(&mut self)
+    where
+        V: PartialEq,
+    {
+        self.assert_not_frozen();
+        let mut nodes = Vec::with_capacity(self.len());
+        if let Some(root) = self.root.take() {
+            Node::flatten(root, &mut nodes);
+        }
+        nodes.dedup_by(|node, previous| node.value == previous.value);
+        self.root = Node::build_balanced(nodes);
+    }
+
+    /// Splits the tree into balanced trees of entries satisfying and not satisfying predicate.
+    pub fn partition<F: FnMut(&K, &V) -> bool>(self, mut predicate: F) -> (Self, Self) {
+        let mut nodes = Vec::with_capacity(self.len());
+        if let Some(root) = self.root {
+            Node::flatten(root, &mut nodes);
+        }
+        let (matching, rest): (Vec<_>, Vec<_>) = nodes.into_iter().partition(|node| predicate(&node.key, &node.value));
+        let (mut matching_tree, mut rest_tree) = (Self::new_empty(), Self::new_empty());
+        matching_tree.root = Node::build_balanced(matching);
+        rest_tree.root = Node::build_balanced(rest);
+        (matching_tree, rest_tree)
+    }
+
+    /// Removes entries whose value is considered empty, e.g. entries with empty collections.
+    pub fn prune<F: Fn(&V) -> bool>(&mut self, is_empty: F) {
+        self.retain_collect(|_, value| !is_empty(value));
+    }
+
+    /// Removes entries whose key equals key of preceding entry in inorder succession, the first of them is kept.
+    pub fn dedup_keys(&mut self) {
+        self.assert_not_frozen();
+        let mut nodes = Vec::with_capacity(self.len());
+        if let Some(root) = self.root.take() {
+            Node::flatten(root, &mut nodes);
+        }
+        nodes.dedup_by(|node, previous| node.key == previous.key);
+        self.root = Node::build_balanced(nodes);
+    }
+
+    /// Clones entries into balanced tree keyed by `Reverse`, so it iterates in descending order of original keys.
+    pub fn reversed(&self) -> Tree<Reverse<K>, V, I>
+    where
+        K: Clone,
+        V: Clone,
+        I: Balancing<Reverse<K>, V>,
+    {
+        let mut entries: Vec<(&K, &V)> = self.iter().collect();
+        entries.reverse();
+        let mut tree = Tree::new_empty();
+        tree.root = Node::build_balanced(entries.into_iter()
+            .map(|(key, value)| Node::detached(Reverse(key.clone()), value.clone()))
+            .collect());
+        tree
+    }
+
+    /// Keeps `k` entries with the largest values, from entries with equal values those with larger keys are kept.
+    /// The tree is rebuilt from kept nodes, so it stays balanced.
+    pub fn retain_top_k_by_value(&mut self, k: usize)
+    where
+        V: Ord,
+    {
+        self.assert_not_frozen();
+        let mut nodes = Vec::with_capacity(self.len());
+        if let Some(root) = self.root.take() {
+            Node::flatten(root, &mut nodes);
+        }
+        if k < nodes.len() {
+            // nodes are sorted by keys, so index breaks ties of values same way as key
+            let mut ranking: Vec<usize> = (0..nodes.len()).collect();
+            ranking.select_nth_unstable_by(k, |&a, &b| nodes[b].value.cmp(&nodes[a].value).then(b.cmp(&a)));
+            let mut kept = vec![false; nodes.len()];
+            for &index in &ranking[..k] {
+                kept[index] = true;
+            }
+            let mut kept = kept.into_iter();
+            nodes.retain(|_| kept.next() == Some(true));
+        }
+        self.root = Node::build_balanced(nodes);
+    }
+
+    /// Removes every key missing in other tree. Both trees are passed once in ascending order, kept nodes are rebuilt
+    /// into balanced tree, so it runs in O(n + m).
+    pub fn retain_intersection(&mut self, other: &Self) {
+        self.assert_not_frozen();
+        let mut nodes = Vec::with_capacity(self.len());
+        if let Some(root) = self.root.take() {
+            Node::flatten(root, &mut nodes);
+        }
+        let mut others = other.iter().peekable();
+        nodes.retain(|node| {
+            while others.next_if(|(key, _)| **key < node.key).is_some() {}
+            others.next_if(|(key, _)| **key == node.key).is_some()
+        });
+        self.root = Node::build_balanced(nodes);
+    }
+
+    /// Removes entries with keys in given range and returns them in ascending order, the rest is rebuilt in O(n).
+    pub fn take_range<R: RangeBounds<K>>(&mut self, range: R) -> Vec<(K, V)> {
+        self.assert_not_frozen();
+        let start = self.rank_of_lower_bound(range.start_bound());
+        let end = self.rank_of_upper_bound(range.end_bound()).max(start);
+        let mut nodes = Vec::with_capacity(self.len());
+        if let Some(root) = self.root.take() {
+            Node::flatten(root, &mut nodes);
+        }
+        let taken: Vec<(K, V)> = nodes.drain(start..end).map(|node| {
+            let Node { key, value, .. } = *node;
+            (key, value)
+        }).collect();
+        self.root = Node::build_balanced(nodes);
+        taken
+    }
+
+    /// Keeps `rank` smallest entries in the tree and returns the rest as new balanced tree.
+    /// If rank is not smaller than length of the tree, returned tree is empty.
+    /// Both trees are rebuilt from all nodes in O(n), even if rank is close to either end.
+    pub fn split_at_rank(&mut self, rank: usize) -> Self {
+        self.assert_not_frozen();
+        let mut nodes = Vec::with_capacity(self.len());
+        if let Some(root) = self.root.take() {
+            Node::flatten(root, &mut nodes);
+        }
+        let rest = nodes.split_off(rank.min(nodes.len()));
+        self.root = Node::build_balanced(nodes);
+        let mut tree = Self::new_empty();
+        tree.root = Node::build_balanced(rest);
+        tree
+    }
+
+    /// Splits the tree by rank into `n` balanced trees with contiguous key ranges in ascending order,
+    /// their lengths differ at most by one, so some of them are empty if `n` is greater than length. Panics if `n` is 0.
+    pub fn split_n_ways(self, n: usize) -> Vec<Self> {
+        assert!(n > 0, "number of parts must be positive");
+        let mut nodes = Vec::with_capacity(self.len());
+        if let Some(root) = self.root {
+            Node::flatten(root, &mut nodes);
+        }
+        let (part_len, longer_parts) = (nodes.len() / n, nodes.len() % n);
+        let mut nodes = nodes.into_iter();
+        (0..n).map(|part| {
+            let mut tree = Self::new_empty();
+            let len = part_len + usize::from(part < longer_parts);
+            tree.root = Node::build_balanced(nodes.by_ref().take(len).collect());
+            tree
+        }).collect()
+    }
+
+    /// Keeps only entries satisfying predicate and returns removed entries in ascending order of keys.
+    /// Predicate is called once for each entry in ascending order, then the tree is rebuilt from kept nodes.
+    pub fn retain_collect<F: FnMut(&K, &mut V) -> bool>(&mut self, mut predicate: F) -> Vec<(K, V)> {
+        self.assert_not_frozen();
+        let mut nodes = Vec::with_capacity(self.len());
+        if let Some(root) = self.root.take() {
+            Node::flatten(root, &mut nodes);
+        }
+
+        let mut kept = Vec::with_capacity(nodes.len());
+        let mut removed = Vec::new();
+        for mut node in nodes {
+            if predicate(&node.key, &mut node.value) {
+                kept.push(node);
+            } else {
+                let Node { key, value, .. } = *node;
+                removed.push((key, value));
+            }
+        }
+        self.root = Node::build_balanced(kept);
+        removed
+    }
+
+    /// Inserts all items and rebuilds the tree, if items contain same key multiple times, the last value is kept.
+    /// Items are collected and sorted first, so O(m) memory is allocated for m items.
+    pub fn bulk_insert<T: IntoIterator<Item=(K, V)>>(&mut self, items: T) {
+        self.assert_not_frozen();
+        let mut items: Vec<(K, V)> = items.into_iter().collect();
+        // sort is stable, so the last item from run of same keys is the last inserted one
+        items.sort_by(|a, b| a.0.cmp(&b.0));
+        self.merge_sorted(items.into_iter());
+    }
+
+    /// Inserts items yielded in ascending order of keys by linear merge with existing entries, so no item is
+    /// collected and no rotation is performed. The last value is kept for same keys like in bulk_insert.
+    pub fn extend_sorted<T: IntoIterator<Item=(K, V)>>(&mut self, items: T)
+    where
+        T::IntoIter: SortedByKey,
+    {
+        self.assert_not_frozen();
+        self.merge_sorted(items.into_iter());
+    }
+
+    // merges items sorted by keys with existing nodes and rebuilds the tree
+    fn merge_sorted<T: Iterator<Item=(K, V)>>(&mut self, items: T) {
+        let mut existing = Vec::new();
+        if let Some(root) = self.root.take() {
+            Node::flatten(root, &mut existing);
+        }
+
+        let mut nodes = Vec::with_capacity(existing.len() + items.size_hint().0);
+        let mut existing = existing.into_iter().peekable();
+        let mut items = items.peekable();
+        while let Some((key, value)) = items.next() {
+            if items.peek().is_some_and(|(next_key, _)| *next_key == key) {
+                // value will be overwritten by later item
+                continue;
+            }
+            while let Some(node) = existing.next_if(|node| node.key < key) {
+                nodes.push(node);
+            }
+            match existing.next_if(|node| node.key == key) {
+                Some(mut node) => {
+                    node.key = key;
+                    node.value = value;
+                    nodes.push(node);
+                }
+                None => {
+                    nodes.push(Node::detached(key, value));
+                }
+            }
+        }
+        nodes.extend(existing);
+        debug_assert!(nodes.windows(2).all(|pair| pair[0].key < pair[1].key), "items must be sorted by keys");
+
+        self.root = Node::build_balanced(nodes);
+    }
+}
+
+// builds the tree in linear time from items already sorted by keys
+impl<K: Ord, V, I: Balancing<K, V>> TryFrom<Vec<(K, V)>> for Tree<K, V, I> {
+    type Error = UnsortedError;
+
+    fn try_from(items: Vec<(K, V)>) -> Result<Self, Self::Error> {
+        for (index, pair) in items.windows(2).enumerate() {
+            match pair[0].0.cmp(&pair[1].0) {
+                Ordering::Less => {}
+                Ordering::Equal => return Err(UnsortedError::DuplicateKey(index + 1)),
+                Ordering::Greater => return Err(UnsortedError::OutOfOrder(index + 1)),
+            }
+        }
+        let mut tree = Self::new_empty();
+        tree.root = Node::build_balanced(items.into_iter().map(|(key, value)| Node::detached(key, value)).collect());
+        Ok(tree)
+    }
+}
+
+// items are collected to find out whether they are sorted, because Extend cannot be specialized for SortedByKey,
+// sorted items are merged in O(n + m) when it beats O(m log(n + m)) of inserting them one by one
+impl<K: Ord, V, I: Balancing<K, V>> Extend<(K, V)> for Tree<K, V, I> {
+    fn extend<T: IntoIterator<Item=(K, V)>>(&mut self, items: T) {
+        self.assert_not_frozen();
+        let items: Vec<(K, V)> = items.into_iter().collect();
+        let total = self.len() + items.len();
+        let sorted = items.windows(2).all(|pair| pair[0].0 <= pair[1].0);
+        if sorted && items.len() * (total.checked_ilog2().unwrap_or(0) as usize + 1) >= total {
+            self.merge_sorted(items.into_iter());
+        } else {
+            for (key, value) in items {
+                I::insert(self, key, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Reverse;
+    use std::collections::BTreeMap;
+    use std::ops::{Bound, RangeBounds};
+    use super::{counting_rotations, Child, Diff, Node, Tree, UnsortedError};
+    use crate::rb::Color;
+    use crate::avl::AVL;
+    use crate::rb::RedBlack;
+
+    #[test]
+    fn test_new_trees_are_empty() {
+        let trees: [AVL<u32, u32>; 3] = [AVL::new(), AVL::default(), Tree::new_empty()];
+        for tree in trees {
+            assert!(tree.is_empty());
+            assert_eq!(0, tree.len());
+            assert_eq!(None, tree.min());
+        }
+        let trees: [RedBlack<u32, u32>; 3] = [RedBlack::new(), RedBlack::default(), Tree::new_empty()];
+        for tree in trees {
+            assert!(tree.is_empty());
+            assert_eq!(0, tree.len());
+        }
+
+        let mut tree = AVL::new();
+        tree.insert(1, 1);
+        tree.insert(2, 2);
+        assert!(!tree.is_empty());
+        assert_eq!(2, tree.len());
+    }
+
+    #[test]
+    fn test_node_count_matches_len() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        // simple linear congruential generator gives reproducible pseudo random workload
+        let mut random = 12345u64;
+        for _ in 0..5000 {
+            random = random.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            let key = (random >> 33) % 500;
+            if random.is_multiple_of(3) {
+                avl.remove(&key);
+                rb.remove(&key);
+            } else {
+                avl.insert(key, random);
+                rb.insert(key, random);
+            }
+            assert_eq!(avl.len(), avl.node_count());
+            assert_eq!(rb.len(), rb.node_count());
+        }
+        assert_eq!(avl.len(), rb.len());
+
+        // drifted size is reported by len, but not by counting
+        avl.root.as_mut().unwrap().size += 2;
+        assert_eq!(rb.len() + 2, avl.len());
+        assert_eq!(rb.len(), avl.node_count());
+    }
+
+    #[test]
+    fn test_into_sorted_vec_moves_entries() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for key in [5, 3, 8, 1, 4, 7, 9, 2, 6] {
+            // strings are not Copy, so the tree must be moved to get them out
+            avl.insert(key, key.to_string());
+            rb.insert(key, key.to_string());
+        }
+        let expected: Vec<(i32, String)> = (1..10).map(|key| (key, key.to_string())).collect();
+        assert_eq!(expected, avl.into_sorted_vec());
+        assert_eq!(expected, rb.into_sorted_vec());
+        assert!(AVL::<i32, String>::new().into_sorted_vec().is_empty());
+    }
+
+    #[test]
+    fn test_shrink_to_balanced_has_minimal_height() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for key in 0..1000 {
+            avl.insert(key, key);
+            rb.insert(key, key);
+        }
+        avl.shrink_to_balanced();
+        rb.shrink_to_balanced();
+        // 1000 nodes fit into 10 levels
+        assert_eq!(Some(9), avl.iter().map(|(key, _)| avl.depth_of(key).unwrap()).max());
+        assert_eq!(Some(9), rb.iter().map(|(key, _)| rb.depth_of(key).unwrap()).max());
+        assert_eq!((0..1000).collect::<Vec<_>>(), avl.inorder_keys().into_iter().copied().collect::<Vec<_>>());
+        assert_eq!(1000, rb.node_count());
+
+        // rebuilt trees are still valid for balancing algorithms
+        for key in 1000..1100 {
+            avl.insert(key, key);
+            rb.insert(key, key);
+        }
+        for key in 0..500 {
+            assert_eq!(Some((key, key)), avl.remove(&key));
+            assert_eq!(Some((key, key)), rb.remove(&key));
+        }
+        assert_eq!(avl.inorder_keys(), rb.inorder_keys());
+    }
+
+    #[test]
+    fn test_range_endpoints_match_iteration() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for key in (0..100).step_by(3) {
+            avl.insert(key, key * 2);
+            rb.insert(key, key * 2);
+        }
+        let ranges = [(Bound::Unbounded, Bound::Unbounded), (Bound::Included(10), Bound::Excluded(40)),
+            (Bound::Excluded(9), Bound::Included(39)), (Bound::Included(9), Bound::Excluded(39)),
+            (Bound::Included(10), Bound::Included(11)), (Bound::Excluded(9), Bound::Excluded(12)),
+            (Bound::Included(50), Bound::Excluded(20)), (Bound::Included(200), Bound::Unbounded),
+            (Bound::Unbounded, Bound::Excluded(0)), (Bound::Included(99), Bound::Unbounded)];
+        for range in ranges {
+            let mut entries = avl.iter().filter(|(key, _)| range.contains(*key));
+            let first = entries.next();
+            let expected = first.map(|first| (first, entries.last().unwrap_or(first)));
+            assert_eq!(expected, avl.range_endpoints(range), "{:?}", range);
+            assert_eq!(expected, rb.range_endpoints(range), "{:?}", range);
+        }
+        assert_eq!(None, AVL::<u32, u32>::new().range_endpoints(..));
+    }
+
+    #[test]
+    fn test_retain_collect_returns_complement() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for key in 0..200 {
+            avl.insert(key, key.to_string());
+            rb.insert(key, key.to_string());
+        }
+        let keep = |key: &i32, value: &mut String| {
+            value.push('!');
+            key % 3 != 0
+        };
+        let removed = avl.retain_collect(keep);
+        assert_eq!(removed, rb.retain_collect(keep));
+        assert_eq!((0..200).step_by(3).map(|key| (key, format!("{}!", key))).collect::<Vec<_>>(), removed);
+        assert_eq!(200 - removed.len(), avl.len());
+        assert!(avl.iter().all(|(key, value)| key % 3 != 0 && *value == format!("{}!", key)));
+        assert!(avl.iter().eq(rb.iter()));
+        assert_eq!(rb.len(), rb.node_count());
+    }
+
+    #[test]
+    fn test_memory_bytes_grows_with_inserts() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        assert_eq!(0, avl.memory_bytes());
+        assert_eq!(0, rb.memory_bytes());
+        let (mut avl_bytes, mut rb_bytes) = (0, 0);
+        for key in 0..100u64 {
+            avl.insert(key, key);
+            rb.insert(key, key);
+            assert!(avl.memory_bytes() > avl_bytes);
+            assert!(rb.memory_bytes() > rb_bytes);
+            avl_bytes = avl.memory_bytes();
+            rb_bytes = rb.memory_bytes();
+        }
+        // node holds at least key and value
+        assert!(avl_bytes > 100 * std::mem::size_of::<(u64, u64)>());
+        avl.insert(0, 1);
+        assert_eq!(avl_bytes, avl.memory_bytes());
+    }
+
+    #[test]
+    fn test_get_or_insert_reports_insertion() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        // ascending keys cause rotations, which must not invalidate returned reference
+        for key in 0..100 {
+            let (value, inserted) = avl.get_or_insert(key, key);
+            assert!(inserted);
+            *value += 1;
+            let (value, inserted) = rb.get_or_insert(key, key);
+            assert!(inserted);
+            *value += 1;
+        }
+        for key in 0..100 {
+            let (value, inserted) = avl.get_or_insert(key, 0);
+            assert!(!inserted);
+            assert_eq!(key + 1, *value);
+            let (value, inserted) = rb.get_or_insert(key, 0);
+            assert!(!inserted);
+            assert_eq!(key + 1, *value);
+        }
+        assert_eq!(100, avl.len());
+        assert_eq!(100, rb.len());
+    }
+
+    #[test]
+    fn test_split_at_rank_partitions_by_position() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for key in 0..100 {
+            avl.insert(key * 2, key);
+            rb.insert(key * 2, key);
+        }
+        let avl_rest = avl.split_at_rank(40);
+        let rb_rest = rb.split_at_rank(40);
+        assert_eq!(40, avl.len());
+        assert_eq!(60, avl_rest.len());
+        assert!(avl.iter().map(|(_, value)| *value).eq(0..40));
+        assert!(avl_rest.iter().map(|(_, value)| *value).eq(40..100));
+        assert!(avl.iter().eq(rb.iter()));
+        assert!(avl_rest.iter().eq(rb_rest.iter()));
+
+        // both parts stay usable for balancing algorithms
+        let mut rb_rest = rb_rest;
+        rb_rest.insert(1, 0);
+        assert_eq!(Some((80, 40)), rb_rest.remove(&80));
+        assert_eq!(60, rb_rest.node_count());
+
+        assert!(avl.split_at_rank(40).is_empty());
+        assert_eq!(40, avl.split_at_rank(0).len());
+        assert!(avl.is_empty());
+    }
+
+    #[test]
+    fn test_merge_with_sums_collided_counts() {
+        let mut first = AVL::new();
+        let mut second = AVL::new();
+        for word in ["a", "b", "c", "a"] {
+            *first.entry(word).or_insert(0) += 1;
+        }
+        for word in ["c", "d", "a", "c"] {
+            *second.entry(word).or_insert(0) += 1;
+        }
+        first.merge_with(second, |_, own, other| own + other);
+        assert_eq!(vec![(&"a", &3), (&"b", &1), (&"c", &3), (&"d", &1)], first.iter().collect::<Vec<_>>());
+
+        let mut rb = RedBlack::new();
+        let mut other = RedBlack::new();
+        for key in 0..100 {
+            rb.insert(key * 2, 1);
+            other.insert(key * 3, 10);
+        }
+        rb.merge_with(other, |key, own, other| {
+            assert_eq!(0, key % 6);
+            own + other
+        });
+        assert_eq!(166, rb.node_count());
+        assert_eq!(Some(&11), rb.find(&6));
+        assert_eq!(Some(&10), rb.find(&3));
+        assert_eq!(Some(&1), rb.find(&4));
+        rb.insert(1, 0);
+        assert_eq!(Some((6, 11)), rb.remove(&6));
+    }
+
+    #[test]
+    fn test_cloned_iter_continues_independently() {
+        struct NotClone(u32);
+        let mut tree = RedBlack::new();
+        for key in 0..20 {
+            tree.insert(key, NotClone(key));
+        }
+        let mut iter = tree.iter();
+        iter.nth(4);
+        let snapshot = iter.clone();
+        assert!(iter.by_ref().take(5).map(|(key, _)| *key).eq(5..10));
+        assert!(snapshot.map(|(key, value)| (*key, value.0)).eq((5..20).map(|key| (key, key))));
+        assert!(iter.map(|(key, _)| *key).eq(10..20));
+    }
+
+    #[test]
+    fn test_min_walks_left_children() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for key in [50, 30, 70, 20, 40, 60, 80, 10] {
+            avl.insert(key, key * 2);
+            rb.insert(key, key * 2);
+        }
+        assert_eq!(Some((&10, &20)), avl.min());
+        assert_eq!(Some((&10, &20)), rb.min());
+        assert_eq!(Some((&80, &160)), avl.max());
+        avl.remove(&10);
+        assert_eq!(Some((&20, &40)), avl.min());
+    }
+
+    #[test]
+    fn test_next_of_missing_key_on_right_path() {
+        let mut tree = RedBlack::new();
+        for key in [20, 10, 30] {
+            tree.insert(key, ());
+        }
+        // search for 15 turns right at 10 and ends there, the last node where it turned left is 20
+        assert_eq!(Some((&20, &())), tree.next(&15));
+        assert_eq!(Some((&10, &())), tree.next(&5));
+        assert_eq!(Some((&30, &())), tree.next(&25));
+        assert_eq!(None, tree.next(&35));
+        assert_eq!(None, tree.next(&30));
+    }
+
+    #[test]
+    fn test_strict_and_inclusive_neighbors() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for key in (0..50).map(|key| key * 2) {
+            avl.insert(key, key);
+            rb.insert(key, key);
+        }
+        for key in -2..102 {
+            let expected_ceiling = (key..100).find(|key| key % 2 == 0 && *key >= 0);
+            let expected_after = (key + 1..100).find(|key| key % 2 == 0 && *key >= 0);
+            let expected_floor = (0..=key.min(98)).rev().find(|key| key % 2 == 0);
+            let expected_before = (0..key.min(99)).rev().find(|key| key % 2 == 0);
+            assert_eq!(expected_ceiling.as_ref(), avl.ceiling(&key).map(|(key, _)| key));
+            assert_eq!(expected_after.as_ref(), avl.first_after(&key).map(|(key, _)| key));
+            assert_eq!(expected_after.as_ref(), avl.next(&key).map(|(key, _)| key));
+            assert_eq!(expected_floor.as_ref(), avl.floor(&key).map(|(key, _)| key));
+            assert_eq!(expected_before.as_ref(), avl.last_before(&key).map(|(key, _)| key));
+            assert_eq!(expected_ceiling.as_ref(), rb.ceiling(&key).map(|(key, _)| key));
+            assert_eq!(expected_after.as_ref(), rb.first_after(&key).map(|(key, _)| key));
+            assert_eq!(expected_floor.as_ref(), rb.floor(&key).map(|(key, _)| key));
+            assert_eq!(expected_before.as_ref(), rb.last_before(&key).map(|(key, _)| key));
+        }
+
+        // exact match is returned only by inclusive variants
+        assert_eq!(Some((&10, &10)), avl.ceiling(&10));
+        assert_eq!(Some((&10, &10)), avl.floor(&10));
+        assert_eq!(Some((&12, &12)), avl.first_after(&10));
+        assert_eq!(Some((&8, &8)), avl.last_before(&10));
+        assert_eq!(Some((&0, &0)), avl.min());
+        assert_eq!(Some((&0, &0)), rb.min());
+    }
+
+    #[test]
+    fn test_dedup_values_keeps_first_of_run() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for (key, value) in [(0, 'A'), (1, 'A'), (2, 'B'), (3, 'B'), (4, 'A')] {
+            avl.insert(key, value);
+            rb.insert(key, value);
+        }
+        avl.dedup_values();
+        rb.dedup_values();
+        assert_eq!(vec![(&0, &'A'), (&2, &'B'), (&4, &'A')], avl.iter().collect::<Vec<_>>());
+        assert!(avl.iter().eq(rb.iter()));
+        assert_eq!(3, rb.node_count());
+    }
+
+    #[test]
+    fn test_nearest_by_value() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for (key, value) in [('a', 10u32), ('b', 20), ('c', 30)] {
+            avl.insert(key, value);
+            rb.insert(key, value);
+        }
+        assert_eq!(Some((&'b', &20)), avl.nearest_by_value(&23));
+        assert_eq!(Some((&'b', &20)), rb.nearest_by_value(&23));
+        assert_eq!(Some((&'a', &10)), avl.nearest_by_value(&0));
+        assert_eq!(Some((&'c', &30)), avl.nearest_by_value(&100));
+        // 15 is equally far from 10 and 20
+        assert_eq!(Some((&'a', &10)), avl.nearest_by_value(&15));
+        assert_eq!(None, AVL::<char, u32>::new().nearest_by_value(&1));
+    }
+
+    #[test]
+    fn test_height_of_subtree() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for key in 0..7 {
+            avl.insert(key, ());
+            rb.insert(key, ());
+        }
+        // ascending inserts into AVL produce perfect tree with root 3
+        assert_eq!(Some(2), avl.height_of_subtree(&3));
+        assert_eq!(Some(1), avl.height_of_subtree(&1));
+        for leaf in [0, 2, 4, 6] {
+            assert_eq!(Some(0), avl.height_of_subtree(&leaf));
+        }
+        assert_eq!(None, avl.height_of_subtree(&7));
+        for key in 0..7 {
+            let height = rb.height_of_subtree(&key).unwrap();
+            let is_leaf = rb.iter().filter(|(other, _)| rb.path_to(other).unwrap().contains(&&key)).count() == 1;
+            assert_eq!(is_leaf, height == 0);
+        }
+    }
+
+    #[test]
+    fn test_drain_balanced_order_rebuilds_without_rotations() {
+        for count in [0, 1, 2, 10, 64, 100] {
+            let mut tree = RedBlack::new();
+            for key in 0..count {
+                tree.insert(key, key);
+            }
+            let mut expected = AVL::new();
+            for key in 0..count {
+                expected.insert(key, key);
+            }
+            expected.shrink_to_balanced();
+
+            let mut rebuilt = AVL::new();
+            for (key, value) in tree.drain_balanced_order() {
+                rebuilt.insert(key, value);
+            }
+            assert!(tree.is_empty());
+            // rotation would move some node away from its place in minimal height tree
+            for key in 0..count {
+                assert_eq!(expected.path_to(&key), rebuilt.path_to(&key));
+            }
+            assert!(rebuilt.iter().eq(expected.iter()));
+        }
+    }
+
+    #[test]
+    fn test_reduce_range_sums_only_range() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for key in 0..100u64 {
+            avl.insert(key, key * 3);
+            rb.insert(key, key * 3);
+        }
+        let expected: u64 = (25..75).map(|key| key * 3).sum();
+        assert_eq!(expected, avl.reduce_range(25..75, 0, |sum, _, value| sum + value));
+        assert_eq!(expected, rb.reduce_range(25..75, 0, |sum, _, value| sum + value));
+        let keys = avl.reduce_range(95.., Vec::new(), |mut keys, key, _| {
+            keys.push(*key);
+            keys
+        });
+        assert_eq!(vec![95, 96, 97, 98, 99], keys);
+        assert_eq!(0, rb.reduce_range(50..50, 0, |count, _, _| count + 1));
+    }
+
+    #[test]
+    fn test_try_from_sorted_vec() {
+        let items: Vec<(u32, u32)> = (0..100).map(|key| (key, key * 2)).collect();
+        let mut avl = AVL::try_from(items.clone()).unwrap();
+        let mut rb = RedBlack::try_from(items).unwrap();
+        assert_eq!(100, avl.len());
+        assert!(avl.iter().eq(rb.iter()));
+        avl.insert(100, 200);
+        rb.insert(100, 200);
+        assert_eq!(Some((0, 0)), avl.remove(&0));
+        assert_eq!(Some((0, 0)), rb.remove(&0));
+        assert_eq!(rb.len(), rb.node_count());
+        assert!(AVL::<u32, u32>::try_from(Vec::new()).unwrap().is_empty());
+
+        assert_eq!(Some(UnsortedError::OutOfOrder(2)), AVL::try_from(vec![(1, ()), (3, ()), (2, ())]).err());
+        assert_eq!(Some(UnsortedError::DuplicateKey(1)), RedBlack::try_from(vec![(1, ()), (1, ()), (2, ())]).err());
+        assert_eq!("key at index 1 equals the previous one", UnsortedError::DuplicateKey(1).to_string());
+    }
+
+    #[test]
+    fn test_neighbors_match_separate_queries() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for key in (0..60).step_by(3) {
+            avl.insert(key, key);
+            rb.insert(key, key);
+        }
+        for key in -1..62 {
+            assert_eq!((avl.last_before(&key), avl.first_after(&key)), avl.neighbors(&key));
+            assert_eq!((rb.last_before(&key), rb.next(&key)), rb.neighbors(&key));
+        }
+        assert_eq!((Some((&3, &3)), Some((&9, &9))), avl.neighbors(&6));
+        assert_eq!((None, None), AVL::<i32, i32>::new().neighbors(&0));
+    }
+
+    #[test]
+    fn test_count_values_matching_predicate() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for key in 0..100 {
+            avl.insert(key, 99 - key);
+            rb.insert(key, 99 - key);
+        }
+        assert_eq!(50, avl.count_values(|value| value % 2 == 0));
+        assert_eq!(50, rb.count_values(|value| value % 2 == 0));
+        assert_eq!(10, avl.count_values(|value| *value < 10));
+        assert_eq!(0, AVL::<u32, u32>::new().count_values(|_| true));
+    }
+
+    #[test]
+    fn test_apply_range_changes_only_range() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for key in 0..30 {
+            avl.insert(key, key);
+            rb.insert(key, key);
+        }
+        let mut visited = Vec::new();
+        avl.apply_range(10..20, |key, value| {
+            visited.push(*key);
+            *value *= 2;
+        });
+        rb.apply_range(10..20, |_, value| *value *= 2);
+        assert_eq!((10..20).collect::<Vec<_>>(), visited);
+        for key in 0..30 {
+            let expected = if (10..20).contains(&key) { key * 2 } else { key };
+            assert_eq!(Some(&expected), avl.find(&key));
+        }
+        assert!(avl.iter().eq(rb.iter()));
+        assert_eq!(30, rb.node_count());
+        assert_eq!(Some((15, 30)), rb.remove(&15));
+    }
+
+    #[test]
+    fn test_contains_value() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for key in 0..50 {
+            avl.insert(key, key * 10);
+            rb.insert(key, key * 10);
+        }
+        assert!(avl.contains_value(&490));
+        assert!(rb.contains_value(&0));
+        assert!(!avl.contains_value(&15));
+        assert!(!rb.contains_value(&500));
+    }
+
+    #[test]
+    fn test_key_of_value_returns_smallest_key() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for (key, value) in [(5, 'a'), (3, 'b'), (8, 'a'), (1, 'c'), (4, 'a')] {
+            avl.insert(key, value);
+            rb.insert(key, value);
+        }
+        assert_eq!(Some(&4), avl.key_of_value(&'a'));
+        assert_eq!(Some(&4), rb.key_of_value(&'a'));
+        assert_eq!(Some(&1), avl.key_of_value(&'c'));
+        assert_eq!(None, rb.key_of_value(&'d'));
+    }
+
+    #[test]
+    fn test_partition_by_parity() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for key in 0..100 {
+            avl.insert(key, key * 2);
+            rb.insert(key, key * 2);
+        }
+        let (even, odd) = avl.partition(|key, _| key % 2 == 0);
+        let (mut rb_even, rb_odd) = rb.partition(|key, _| key % 2 == 0);
+        assert!(even.iter().map(|(key, _)| *key).eq((0..100).step_by(2)));
+        assert!(odd.iter().map(|(key, value)| (*key, *value)).eq((1..100).step_by(2).map(|key| (key, key * 2))));
+        assert!(even.iter().eq(rb_even.iter()));
+        assert!(odd.iter().eq(rb_odd.iter()));
+        assert_eq!(50, rb_odd.node_count());
+        rb_even.insert(1, 2);
+        assert_eq!(Some((0, 0)), rb_even.remove(&0));
+    }
+
+    #[test]
+    fn test_planted_duplicate_key_is_detected_and_removed() {
+        let mut tree: AVL<u32, ()> = AVL::new();
+        assert!(!tree.has_duplicate_keys());
+        for key in 0..10 {
+            tree.insert(key, ());
+        }
+        assert!(!tree.has_duplicate_keys());
+
+        // 2 is planted again as left child of 3, which cannot be done by insert
+        let shape = [(2, 0), (1, 1), (3, 1), (2, 2)];
+        let mut tree = Tree { root: Some(build_subtree(&shape, |_| 0i8)), frozen: false };
+        assert!(tree.has_duplicate_keys());
+        assert_eq!(4, tree.len());
+        tree.dedup_keys();
+        assert!(!tree.has_duplicate_keys());
+        assert_eq!(vec![&1, &2, &3], tree.inorder_keys());
+        tree.insert(4, ());
+        assert_eq!(Some((2, ())), tree.remove(&2));
+        assert_eq!(3, tree.node_count());
+
+        // 0 is planted as left child of 3, keys are out of order but none of them repeats
+        let tree = Tree { root: Some(build_subtree(&[(2, 0), (1, 1), (3, 1), (0, 2)], |_| 0i8)), frozen: false };
+        assert!(!tree.is_bst());
+        assert!(!tree.has_duplicate_keys());
+    }
+
+    #[test]
+    fn test_iter_levels_groups_by_depth() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for key in 0..7 {
+            avl.insert(key, ());
+            rb.insert(key, ());
+        }
+        let keys = |levels: Vec<Vec<(&i32, &())>>| -> Vec<Vec<i32>> {
+            levels.into_iter().map(|level| level.into_iter().map(|(key, _)| *key).collect()).collect()
+        };
+        assert_eq!(vec![vec![3], vec![1, 5], vec![0, 2, 4, 6]], keys(avl.iter_levels()));
+        // red-black tree leans right after ascending inserts
+        assert_eq!(vec![vec![1], vec![0, 3], vec![2, 5], vec![4, 6]], keys(rb.iter_levels()));
+        assert!(AVL::<i32, ()>::new().iter_levels().is_empty());
+    }
+
+    #[test]
+    fn test_trim_to_smallest_keeps_lowest_keys() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for key in 0..100 {
+            avl.insert(key, key);
+            rb.insert(key, key);
+        }
+        avl.trim_to_smallest(10);
+        rb.trim_to_smallest(10);
+        assert!(avl.iter().map(|(key, _)| *key).eq(0..10));
+        assert!(avl.iter().eq(rb.iter()));
+        avl.trim_to_smallest(20);
+        assert_eq!(10, avl.len());
+        rb.insert(50, 50);
+        assert_eq!(Some((5, 5)), rb.remove(&5));
+        assert_eq!(10, rb.node_count());
+    }
+
+    #[test]
+    fn test_trim_to_largest_keeps_greatest_keys() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for key in 0..100 {
+            avl.insert(key, key);
+            rb.insert(key, key);
+        }
+        avl.trim_to_largest(10);
+        rb.trim_to_largest(10);
+        assert!(avl.iter().map(|(key, _)| *key).eq(90..100));
+        assert!(avl.iter().eq(rb.iter()));
+        avl.trim_to_largest(20);
+        assert_eq!(10, avl.len());
+        rb.insert(0, 0);
+        assert_eq!(Some((95, 95)), rb.remove(&95));
+        assert_eq!(10, rb.node_count());
+    }
+
+    #[test]
+    fn test_pop_first_and_last() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for key in 0..100 {
+            avl.insert(key, key);
+            rb.insert(key, key);
+        }
+        for key in 0..50 {
+            assert_eq!(Some((key, key)), avl.pop_first());
+            assert_eq!(Some((99 - key, 99 - key)), avl.pop_last());
+            assert_eq!(Some((99 - key, 99 - key)), rb.pop_last());
+            assert_eq!(Some((key, key)), rb.pop_first());
+            assert_eq!(avl.len(), avl.node_count());
+            assert_eq!(rb.len(), rb.node_count());
+        }
+        assert_eq!(None, avl.pop_first());
+        assert_eq!(None, rb.pop_last());
+    }
+
+    #[test]
+    fn test_ranks_of_neighbors() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        let keys: Vec<i32> = (0..40).map(|key| key * 5).collect();
+        for key in &keys {
+            avl.insert(*key, ());
+            rb.insert(*key, ());
+        }
+        for key in -3..203 {
+            let successor = keys.iter().position(|other| *other > key);
+            let predecessor = keys.iter().rposition(|other| *other < key);
+            assert_eq!(successor, avl.rank_of_successor(&key));
+            assert_eq!(successor, rb.rank_of_successor(&key));
+            assert_eq!(predecessor, avl.rank_of_predecessor(&key));
+            assert_eq!(predecessor, rb.rank_of_predecessor(&key));
+            let gap = if key % 5 == 0 && (0..195).contains(&key) { Some(0) } else { None };
+            assert_eq!(gap, avl.gap_rank(&key));
+        }
+        // span of keys between predecessor and successor of removed range
+        for key in 50..100 {
+            avl.remove(&key);
+        }
+        assert_eq!(Some(9), avl.rank_of_predecessor(&75));
+        assert_eq!(Some(10), avl.rank_of_successor(&75));
+    }
+
+    #[test]
+    fn test_find_all_in_collects_range() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for key in 0..100 {
+            avl.insert(key, key * 2);
+            rb.insert(key, key * 2);
+        }
+        let expected: Vec<(i32, i32)> = (20..=30).map(|key| (key, key * 2)).collect();
+        let owned = |entries: Vec<(&i32, &i32)>| -> Vec<(i32, i32)> {
+            entries.into_iter().map(|(key, value)| (*key, *value)).collect()
+        };
+        assert_eq!(expected, owned(avl.find_all_in(20..=30)));
+        assert_eq!(expected, owned(rb.find_all_in(20..31)));
+        assert_eq!(100, avl.find_all_in(..).len());
+        assert!(rb.find_all_in(100..).is_empty());
+    }
+
+    #[test]
+    fn test_iter_with_depth_matches_depth_of() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for key in 0..50 {
+            avl.insert(key, key);
+            rb.insert(key, key);
+        }
+        let root_key = *avl.path_to(&0).unwrap()[0];
+        for (depth, key, value) in avl.iter_with_depth() {
+            assert_eq!(avl.depth_of(key), Some(depth));
+            assert_eq!(key, value);
+            assert_eq!(depth == 0, *key == root_key);
+        }
+        assert!(rb.iter_with_depth().all(|(depth, key, _)| rb.depth_of(key) == Some(depth)));
+        assert!(rb.iter_with_depth().map(|(_, key, _)| *key).eq(0..50));
+        assert_eq!(None, AVL::<u32, u32>::new().iter_with_depth().next());
+    }
+
+    #[test]
+    fn test_to_intervals_coalesces_consecutive_keys() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for (key, value) in [(1, 'A'), (2, 'A'), (3, 'A'), (5, 'B')] {
+            avl.insert(key, value);
+            rb.insert(key, value);
+        }
+        assert_eq!(vec![(1, 3, &'A'), (5, 5, &'B')], avl.to_intervals());
+        assert_eq!(vec![(1, 3, &'A'), (5, 5, &'B')], rb.to_intervals());
+
+        // equal values of non-consecutive keys and different values of consecutive keys are not merged
+        avl.insert(6, 'C');
+        avl.insert(7, 'C');
+        avl.insert(9, 'C');
+        assert_eq!(vec![(1, 3, &'A'), (5, 5, &'B'), (6, 7, &'C'), (9, 9, &'C')], avl.to_intervals());
+
+        let mut bounded = AVL::new();
+        bounded.insert(u8::MAX - 1, ());
+        bounded.insert(u8::MAX, ());
+        assert_eq!(vec![(254, 255, &())], bounded.to_intervals());
+        assert!(AVL::<u8, ()>::new().to_intervals().is_empty());
+    }
+
+    #[test]
+    fn test_key_range_matches_min_and_max() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        assert_eq!(None, avl.key_range());
+        for key in [50, 20, 80, 10, 90, 60] {
+            avl.insert(key, ());
+            rb.insert(key, ());
+            assert_eq!(avl.min().zip(avl.max()).map(|(min, max)| (min.0, max.0)), avl.key_range());
+        }
+        assert_eq!(Some((&10, &90)), avl.key_range());
+        assert_eq!(Some((&10, &90)), rb.key_range());
+    }
+
+    #[test]
+    fn test_find_batch_matches_find() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for key in (0..1000).step_by(7) {
+            avl.insert(key, key * 2);
+            rb.insert(key, key * 2);
+        }
+        let mut random = 42u64;
+        let mut keys: Vec<u64> = (0..300).map(|_| {
+            random = random.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (random >> 33) % 1100
+        }).collect();
+        keys.sort();
+        let expected: Vec<Option<&u64>> = keys.iter().map(|key| avl.find(key)).collect();
+        assert_eq!(expected, avl.find_batch(&keys));
+        assert_eq!(expected, rb.find_batch(&keys));
+        assert!(expected.iter().any(|value| value.is_some()));
+        assert!(avl.find_batch(&[]).is_empty());
+
+        keys.reverse();
+        let expected: Vec<Option<&u64>> = keys.iter().map(|key| rb.find(key)).collect();
+        assert_eq!(expected, rb.find_batch(&keys));
+        assert_eq!(vec![Some(&14), None, Some(&0)], avl.find_batch(&[7, 8, 0]));
+    }
+
+    #[test]
+    fn test_duplicate_value_count() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for (key, value) in [(1, 'a'), (2, 'b'), (3, 'c'), (4, 'b'), (5, 'd')] {
+            avl.insert(key, value);
+            rb.insert(key, value);
+        }
+        assert_eq!(2, avl.duplicate_value_count());
+        assert_eq!(2, rb.duplicate_value_count());
+        avl.insert(6, 'b');
+        avl.insert(7, 'a');
+        assert_eq!(5, avl.duplicate_value_count());
+        assert_eq!(0, AVL::<u32, u32>::new().duplicate_value_count());
+    }
+
+    #[test]
+    fn test_sample_keys_at_quantiles() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for key in 0..100 {
+            avl.insert(key * 10, ());
+            rb.insert(key * 10, ());
+        }
+        assert_eq!(vec![&250, &500, &750], avl.sample_keys(4));
+        assert_eq!(vec![&250, &500, &750], rb.sample_keys(4));
+        assert_eq!(vec![&330, &660], rb.sample_keys(3));
+        assert!(avl.sample_keys(1).is_empty());
+        assert!(avl.sample_keys(0).is_empty());
+        assert!(AVL::<u32, ()>::new().sample_keys(4).is_empty());
+    }
+
+    #[test]
+    fn test_structural_eq_differs_from_contents_eq() {
+        let mut inserted = AVL::new();
+        let mut rb_inserted = RedBlack::new();
+        for key in 0..10 {
+            inserted.insert(key, ());
+            rb_inserted.insert(key, ());
+        }
+        let built = AVL::try_from((0..10).map(|key| (key, ())).collect::<Vec<_>>()).unwrap();
+        let rb_built = RedBlack::try_from((0..10).map(|key| (key, ())).collect::<Vec<_>>()).unwrap();
+        assert!(inserted.iter().eq(built.iter()));
+        assert!(!inserted.structural_eq(&built));
+        assert!(!rb_inserted.structural_eq(&rb_built));
+
+        let mut shrunk = AVL::new();
+        for key in (0..10).rev() {
+            shrunk.insert(key, ());
+        }
+        shrunk.shrink_to_balanced();
+        assert!(shrunk.structural_eq(&built));
+        assert!(inserted.structural_eq(&inserted));
+        assert!(AVL::<u32, ()>::new().structural_eq(&AVL::new()));
+        assert!(!built.structural_eq(&AVL::new()));
+    }
+
+    #[test]
+    fn test_sorted_insert_rotation_count() {
+        for n in 1..200u32 {
+            let mut tree = AVL::new();
+            let mut rotations = 0;
+            for key in 0..n {
+                tree.insert_counting_rotations(key, (), &mut rotations);
+            }
+            // every insert rotates once, except those making the tree perfect and growing its height
+            assert_eq!(u64::from(n - n.ilog2() - 1), rotations);
+        }
+
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        let (mut avl_rotations, mut rb_rotations) = (0, 0);
+        for key in 0..1000 {
+            avl.insert_counting_rotations(key, (), &mut avl_rotations);
+            rb.insert_counting_rotations(key, (), &mut rb_rotations);
+        }
+        assert!(rb_rotations < avl_rotations);
+        let mut rotations = 0;
+        assert_eq!(Some((500, ())), avl.remove_counting_rotations(&500, &mut rotations));
+        assert_eq!(None, rb.remove_counting_rotations(&1000, &mut rotations));
+    }
+
+    #[test]
+    fn test_upsert_batch_accumulates_counts() {
+        let events = ["login", "click", "click", "logout", "click", "login"];
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        avl.upsert_batch(events.iter().map(|event| (*event, 1)), |total, count| *total += count);
+        rb.upsert_batch(events.iter().map(|event| (*event, 1)), |total, count| *total += count);
+        assert_eq!(vec![(&"click", &3), (&"login", &2), (&"logout", &1)], avl.iter().collect::<Vec<_>>());
+        assert!(avl.iter().eq(rb.iter()));
+
+        avl.upsert_batch([("click", 10), ("scroll", 5)], |total, count| *total += count);
+        assert_eq!(Some(&13), avl.find(&"click"));
+        assert_eq!(Some(&5), avl.find(&"scroll"));
+    }
+
+    #[test]
+    fn test_prune_removes_empty_collections() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for key in 0..50 {
+            let items: Vec<u32> = (0..key % 4).collect();
+            avl.insert(key, items.clone());
+            rb.insert(key, items);
+        }
+        avl.prune(Vec::is_empty);
+        rb.prune(|items| items.is_empty());
+        assert_eq!(37, avl.len());
+        assert!(avl.iter().all(|(key, items)| key % 4 != 0 && !items.is_empty()));
+        assert!(avl.iter().eq(rb.iter()));
+        assert_eq!(37, rb.node_count());
+    }
+
+    #[test]
+    fn test_first_n_in_range() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for key in 0..200 {
+            avl.insert(key, key);
+            rb.insert(key, key);
+        }
+        assert_eq!(vec![(&10, &10), (&11, &11), (&12, &12)], avl.first_n_in(10..100, 3));
+        assert_eq!(vec![(&10, &10), (&11, &11), (&12, &12)], rb.first_n_in(10..100, 3));
+        assert_eq!(2, avl.first_n_in(10..12, 3).len());
+        assert!(rb.first_n_in(300.., 3).is_empty());
+        assert!(avl.range(10..100).eq(rb.range(10..100)));
+        assert!(avl.range(..=5).map(|(key, _)| *key).eq(0..=5));
+    }
+
+    #[test]
+    fn test_last_n_in_range() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for key in 0..200 {
+            avl.insert(key, key);
+            rb.insert(key, key);
+        }
+        assert_eq!(vec![(&97, &97), (&98, &98), (&99, &99)], avl.last_n_in(10..100, 3));
+        assert_eq!(vec![(&97, &97), (&98, &98), (&99, &99)], rb.last_n_in(10..100, 3));
+        assert_eq!(vec![(&10, &10), (&11, &11)], avl.last_n_in(10..12, 3));
+        assert_eq!(vec![(&199, &199)], rb.last_n_in(.., 1));
+        assert!(avl.last_n_in(300.., 3).is_empty());
+        assert!(avl.last_n_in(10..100, 0).is_empty());
+        assert!(AVL::<u32, u32>::new().last_n_in(.., 3).is_empty());
+    }
+
+    #[test]
+    fn test_move_min_to_transfers_all_entries() {
+        let mut source = RedBlack::new();
+        let mut destination = RedBlack::new();
+        for key in 0..100 {
+            source.insert(key * 2, 'a');
+            destination.insert(key * 3, 'b');
+        }
+        let mut moved = 0;
+        while source.move_min_to(&mut destination) {
+            moved += 1;
+            assert_eq!(100 - moved, source.node_count());
+        }
+        assert_eq!(100, moved);
+        assert!(source.is_empty());
+        assert_eq!(166, destination.node_count());
+        assert_eq!(Some(&'a'), destination.find(&6));
+        assert_eq!(Some(&'b'), destination.find(&3));
+
+        let mut avl = AVL::new();
+        let mut other = AVL::new();
+        avl.insert(1, ());
+        assert!(avl.move_min_to(&mut other));
+        assert!(!avl.move_min_to(&mut other));
+        assert_eq!(Some((&1, &())), other.min());
+    }
+
+    #[test]
+    fn test_collect_into_appends_to_buffer() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for key in [3, 1, 2] {
+            avl.insert(key, key.to_string());
+            rb.insert(key + 10, key.to_string());
+        }
+        let mut buffer = Vec::new();
+        avl.collect_into(&mut buffer);
+        rb.collect_into(&mut buffer);
+        let expected: Vec<(i32, String)> = [1, 2, 3, 11, 12, 13].iter()
+            .map(|key| (*key, (key % 10).to_string()))
+            .collect();
+        assert_eq!(expected, buffer);
+
+        let capacity = buffer.capacity();
+        buffer.clear();
+        avl.collect_into(&mut buffer);
+        assert_eq!(3, buffer.len());
+        assert_eq!(capacity, buffer.capacity());
+    }
+
+    #[test]
+    fn test_is_bst_detects_planted_node() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        assert!(avl.is_bst());
+        for key in [5, 2, 8, 1, 9, 3] {
+            avl.insert(key, ());
+            rb.insert(key, ());
+        }
+        assert!(avl.is_bst());
+        assert!(rb.is_bst());
+
+        // 4 is planted into right subtree of 6, but it is smaller than 6
+        let shape = [(6, 0), (2, 1), (8, 1), (4, 2)];
+        let tree = Tree { root: Some(build_subtree(&shape, |_| Color::Black)), frozen: false };
+        assert!(!tree.is_bst());
+        let tree = Tree { root: Some(build_subtree(&[(6, 0), (2, 1), (8, 1), (7, 2)], |_| 0i8)), frozen: false };
+        assert!(tree.is_bst());
+    }
+
+    #[test]
+    fn test_find_or_nearest_snaps_to_closer_key() {
+        fn check_snapping<I>(tree: &Tree<i32, i32, I>) {
+            assert_eq!(Some((&20, &40, true)), tree.find_or_nearest(&20));
+            assert_eq!(Some((&40, &80, false)), tree.find_or_nearest(&45));
+            assert_eq!(Some((&80, &160, false)), tree.find_or_nearest(&61));
+            // 30 is equally far from 20 and 40
+            assert_eq!(Some((&20, &40, false)), tree.find_or_nearest(&30));
+            assert_eq!(Some((&10, &20, false)), tree.find_or_nearest(&0));
+            assert_eq!(Some((&80, &160, false)), tree.find_or_nearest(&1000));
+        }
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for key in [10, 20, 40, 80] {
+            avl.insert(key, key * 2);
+            rb.insert(key, key * 2);
+        }
+        check_snapping(&avl);
+        check_snapping(&rb);
+        assert_eq!(None, AVL::<i32, i32>::new().find_or_nearest(&1));
+
+        // distances between extreme keys do not fit into the key type
+        let mut signed = AVL::new();
+        let mut unsigned = RedBlack::new();
+        for (key, small_key) in [(i32::MIN, 0u8), (i32::MAX, 255)] {
+            signed.insert(key, ());
+            unsigned.insert(small_key, ());
+        }
+        assert_eq!(Some((&i32::MIN, &(), false)), signed.find_or_nearest(&-1));
+        assert_eq!(Some((&i32::MAX, &(), false)), signed.find_or_nearest(&0));
+        assert_eq!(Some((&255, &(), false)), unsigned.find_or_nearest(&200));
+        assert_eq!(Some((&0, &(), false)), unsigned.find_or_nearest(&127));
+    }
+
+    #[test]
+    fn test_batch_apply_updates_only_existing_keys() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for key in 0..10 {
+            avl.insert(key, 0);
+            rb.insert(key, 0);
+        }
+        let updates = [(2, 20), (5, 50), (15, 150), (-1, 10), (5, 55)];
+        avl.batch_apply(updates);
+        rb.batch_apply(updates);
+        assert_eq!(10, avl.len());
+        assert_eq!(None, avl.find(&15));
+        assert_eq!(Some(&20), avl.find(&2));
+        assert_eq!(Some(&55), avl.find(&5));
+        assert_eq!(Some(&0), avl.find(&3));
+        assert!(avl.iter().eq(rb.iter()));
+    }
+
+    #[test]
+    fn test_difference_update_removes_shared_keys() {
+        let mut tree = AVL::new();
+        let mut evens = AVL::new();
+        for key in 0..100 {
+            tree.insert(key, key);
+            evens.insert(key * 2, 0);
+        }
+        tree.difference_update(&evens);
+        assert_eq!((0..50).map(|key| key * 2 + 1).collect::<Vec<_>>(), tree.iter().map(|(key, _)| *key).collect::<Vec<_>>());
+        assert!(tree.is_valid());
+        assert_eq!(100, evens.len());
+
+        evens.difference_update(&AVL::new());
+        assert_eq!(100, evens.len());
+    }
+
+    #[test]
+    fn test_max_recursion_depth_stays_bounded() {
+        let mut tree = AVL::new();
+        assert_eq!(0, tree.max_recursion_depth());
+        tree.insert(0, ());
+        assert_eq!(1, tree.max_recursion_depth());
+        for key in 1..100000 {
+            tree.insert(key, ());
+        }
+        // AVL height is below 1.45 * log2(n)
+        assert!(tree.max_recursion_depth() < 40);
+    }
+
+    #[test]
+    fn test_merge_with_iter_interleaves_streams() {
+        let mut evens = RedBlack::new();
+        for key in 0..10 {
+            evens.insert(key * 2, 'e');
+        }
+        let merged: Vec<(i32, char)> = evens.merge_with_iter((0..10).map(|key| (key * 2 + 1, 'o'))).collect();
+        assert_eq!((0..20).map(|key| (key, if key % 2 == 0 { 'e' } else { 'o' })).collect::<Vec<_>>(), merged);
+
+        // stream wins on shared keys
+        let merged: Vec<(i32, char)> = evens.merge_with_iter([(-1, 's'), (4, 's'), (30, 's')]).collect();
+        assert_eq!(12, merged.len());
+        assert_eq!(vec![(-1, 's'), (0, 'e'), (2, 'e'), (4, 's')], merged[..4]);
+        assert_eq!(Some(&(30, 's')), merged.last());
+    }
+
+    #[test]
+    fn test_missing_in_finds_holes() {
+        let mut tree = AVL::new();
+        for key in [0u8, 1, 3, 4] {
+            tree.insert(key, ());
+        }
+        assert_eq!(vec![2], tree.missing_in(0..=4));
+        assert_eq!(vec![2, 5, 6], tree.missing_in(1..7));
+        assert_eq!(vec![2], tree.missing_in(..));
+        assert_eq!(vec![5], tree.missing_in((Bound::Excluded(3), Bound::Included(5))));
+        assert_eq!(vec![2], tree.missing_in(2..));
+        assert_eq!([2].into_iter().chain(5..=255).collect::<Vec<u8>>(), tree.missing_in(2..=255));
+        assert!(tree.missing_in(3..3).is_empty());
+        assert_eq!(vec![0, 1], AVL::<u8, ()>::new().missing_in(0..2));
+    }
+
+    #[test]
+    fn test_split_n_ways_gives_contiguous_parts() {
+        let mut tree = RedBlack::new();
+        for key in 0..100 {
+            tree.insert(key, ());
+        }
+        let parts = tree.split_n_ways(4);
+        assert_eq!(vec![25, 25, 25, 25], parts.iter().map(|part| part.len()).collect::<Vec<_>>());
+        assert!(parts.iter().all(|part| part.is_valid()));
+        let keys: Vec<i32> = parts.iter().flat_map(|part| part.iter().map(|(key, _)| *key)).collect();
+        assert_eq!((0..100).collect::<Vec<_>>(), keys);
+
+        let mut tree = AVL::new();
+        for key in 0..5 {
+            tree.insert(key, ());
+        }
+        let parts = tree.split_n_ways(3);
+        assert_eq!(vec![2, 2, 1], parts.iter().map(|part| part.len()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_diff_reports_changes_in_key_order() {
+        let mut old = AVL::new();
+        let mut new = AVL::new();
+        for (key, value) in [(1, 'a'), (2, 'b'), (3, 'c'), (5, 'e')] {
+            old.insert(key, value);
+        }
+        for (key, value) in [(0, 'z'), (2, 'b'), (3, 'x'), (4, 'd'), (5, 'y')] {
+            new.insert(key, value);
+        }
+        assert_eq!(vec![
+            Diff::Added(&0, &'z'),
+            Diff::Removed(&1, &'a'),
+            Diff::Changed(&3, &'c', &'x'),
+            Diff::Added(&4, &'d'),
+            Diff::Changed(&5, &'e', &'y'),
+        ], old.diff(&new).collect::<Vec<_>>());
+        assert_eq!(0, old.diff(&old).count());
+        assert_eq!(4, AVL::new().diff(&old).count());
+    }
+
+    #[test]
+    fn test_apply_diff_syncs_trees() {
+        let mut tree = RedBlack::new();
+        let mut target = RedBlack::new();
+        for key in 0..100 {
+            tree.insert(key, key % 7);
+            target.insert(key + 30, key % 5);
+        }
+        let diff: Vec<Diff<i32, i32>> = tree.diff(&target).map(Diff::cloned).collect();
+        tree.apply_diff(diff);
+        assert!(tree.iter().eq(target.iter()));
+        assert!(tree.is_valid());
+        assert_eq!(0, tree.diff(&target).count());
+    }
+
+    #[test]
+    fn test_first_key_with_value_at_least_scans_from_smallest_key() {
+        let mut tree = AVL::new();
+        for (time, level) in [(10, 1), (20, 4), (30, 9), (40, 6), (50, 2)] {
+            tree.insert(time, level);
+        }
+        assert_eq!(Some((&20, &4)), tree.first_key_with_value_at_least(&3));
+        assert_eq!(Some((&30, &9)), tree.first_key_with_value_at_least(&7));
+        assert_eq!(Some((&10, &1)), tree.first_key_with_value_at_least(&0));
+        assert_eq!(None, tree.first_key_with_value_at_least(&10));
+    }
+
+    #[test]
+    fn test_extend_sorted_merges_without_rotations() {
+        let mut tree = AVL::new();
+        let mut other = AVL::new();
+        for key in 0..100 {
+            tree.insert(key * 2, 'a');
+            other.insert(key * 3, 'b');
+        }
+        let mut expected = AVL::new();
+        for (key, value) in tree.iter().chain(other.iter()) {
+            expected.insert(*key, *value);
+        }
+
+        let mut rotations = 0;
+        counting_rotations(&mut rotations, || tree.extend_sorted(other));
+        assert_eq!(0, rotations);
+        assert!(tree.iter().eq(expected.iter()));
+        assert!(tree.is_valid());
+
+        let map: BTreeMap<i32, char> = (-5..5).map(|key| (key, 'c')).collect();
+        counting_rotations(&mut rotations, || tree.extend_sorted(map));
+        assert_eq!(0, rotations);
+        assert_eq!(Some(&'c'), tree.find(&-5));
+        assert_eq!(Some(&'c'), tree.find(&4));
+        assert_eq!(Some(&'b'), tree.find(&6));
+
+        let mut refs: RedBlack<&i32, &char> = RedBlack::new();
+        refs.extend_sorted(expected.iter());
+        assert_eq!(expected.len(), refs.len());
+        assert!(refs.is_valid());
+    }
+
+    #[test]
+    fn test_extend_merges_sorted_items_without_rotations() {
+        let mut tree = RedBlack::new();
+        let mut other = AVL::new();
+        for key in 0..100 {
+            tree.insert(key * 2, 'a');
+            other.insert(key * 3, 'b');
+        }
+        let mut expected = BTreeMap::new();
+        expected.extend(tree.iter().chain(other.iter()).map(|(key, value)| (*key, *value)));
+
+        // map drops SortedByKey marker, sorted order is found by scanning collected items
+        let mut rotations = 0;
+        counting_rotations(&mut rotations, || tree.extend(other.iter().map(|(key, value)| (*key, *value))));
+        assert_eq!(0, rotations);
+        assert!(tree.iter().eq(expected.iter()));
+        assert!(tree.is_valid());
+
+        // unsorted items are inserted one by one, the last value wins like in insert
+        tree.extend(vec![(1, 'x'), (-1, 'y'), (1, 'z')]);
+        assert_eq!(Some(&'z'), tree.find(&1));
+        assert_eq!(Some(&'y'), tree.find(&-1));
+        // few sorted items are cheaper to insert than merging whole tree
+        tree.extend([(1000, 'c'), (1001, 'c')]);
+        assert_eq!(Some(&'c'), tree.find(&1001));
+        assert_eq!(expected.len() + 4, tree.len());
+        assert!(tree.is_valid());
+
+        let mut empty: AVL<i32, ()> = AVL::new();
+        empty.extend(Vec::new());
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "items must be sorted by keys")]
+    fn test_merge_sorted_rejects_unsorted_items() {
+        let mut tree = AVL::new();
+        tree.insert(2, ());
+        tree.merge_sorted([(3, ()), (1, ())].into_iter());
+    }
+
+    #[test]
+    fn test_try_for_each_stops_at_first_error() {
+        let mut tree = RedBlack::new();
+        for key in 0..20 {
+            tree.insert(key, if key == 7 || key == 12 { -key } else { key });
+        }
+        let mut visited = Vec::new();
+        let result = tree.try_for_each(|key, value| {
+            visited.push(*key);
+            if *value < 0 { Err(*key) } else { Ok(()) }
+        });
+        assert_eq!(Err(7), result);
+        assert_eq!((0..=7).collect::<Vec<_>>(), visited);
+        assert_eq!(Ok(()), tree.try_for_each(|_, _| Ok::<(), ()>(())));
+    }
+
+    #[test]
+    fn test_contains_all_and_any_over_key_slices() {
+        let mut tree = RedBlack::new();
+        for key in 0..10 {
+            tree.insert(key, ());
+        }
+        assert!(tree.contains_all(&[3, 0, 9]));
+        assert!(tree.contains_any(&[3, 0, 9]));
+        assert!(!tree.contains_all(&[3, 10, 9]));
+        assert!(tree.contains_any(&[10, 11, 9]));
+        assert!(!tree.contains_all(&[-1, 10]));
+        assert!(!tree.contains_any(&[-1, 10]));
+        assert!(tree.contains_all(&[]));
+        assert!(!tree.contains_any(&[]));
+    }
+
+    #[test]
+    fn test_retain_top_k_by_value_keeps_largest_values() {
+        let mut tree = AVL::new();
+        for key in 0..100 {
+            tree.insert(key, key);
+        }
+        tree.retain_top_k_by_value(5);
+        assert_eq!((95..100).collect::<Vec<_>>(), tree.iter().map(|(key, _)| *key).collect::<Vec<_>>());
+        assert!(tree.is_valid());
+
+        let mut tree = RedBlack::new();
+        for (key, value) in [(1, 'b'), (2, 'c'), (3, 'a'), (4, 'c'), (5, 'b')] {
+            tree.insert(key, value);
+        }
+        // tie of value 'b' is broken by the larger key
+        tree.retain_top_k_by_value(3);
+        assert_eq!(vec![(&2, &'c'), (&4, &'c'), (&5, &'b')], tree.iter().collect::<Vec<_>>());
+        tree.retain_top_k_by_value(10);
+        assert_eq!(3, tree.len());
+        tree.retain_top_k_by_value(0);
+        assert!(tree.is_empty());
+    }
+
+    #[test]
+    fn test_is_perfect_after_bulk_build() {
+        let mut tree = RedBlack::new();
+        assert!(tree.is_perfect());
+        tree.bulk_insert((0..7).map(|key| (key, ())));
+        assert!(tree.is_perfect());
+        let mut tree = AVL::new();
+        tree.bulk_insert((0..6).map(|key| (key, ())));
+        assert!(!tree.is_perfect());
+        tree.bulk_insert((6..15).map(|key| (key, ())));
+        assert!(tree.is_perfect());
+    }
+
+    #[test]
+    fn test_fold_levels_sums_values_by_depth() {
+        let mut tree = AVL::new();
+        for key in 1..=7 {
+            tree.insert(key, key * 10);
+        }
+        // 4 is the root with children 2, 6 and leaves 1, 3, 5, 7
+        assert_eq!(vec![40, 80, 160], tree.fold_levels(0, |sum, _, value| sum + value));
+        assert_eq!(vec![1, 2, 4], tree.fold_levels(0, |count, _, _| count + 1));
+        assert!(AVL::<i32, i32>::new().fold_levels(0, |sum, _, value| sum + value).is_empty());
+    }
+
+    #[test]
+    fn test_count_between_matches_iterated_range() {
+        let mut tree = RedBlack::new();
+        for key in 0..40 {
+            tree.insert(key * 5, ());
+        }
+        for (lo, hi) in [(0, 200), (3, 17), (5, 5), (5, 6), (-10, 12), (190, 300), (50, 20)] {
+            assert_eq!(tree.iter().filter(|(key, _)| lo <= **key && **key < hi).count(), tree.count_between(&lo, &hi));
+        }
+        assert_eq!(0, tree.count_between(&50, &20));
+    }
+
+    #[test]
+    fn test_rank_range_matches_counted_entries() {
+        let mut tree = AVL::new();
+        for key in 0..50 {
+            tree.insert(key * 3, ());
+        }
+        let ranges = [(Bound::Included(10), Bound::Excluded(40)), (Bound::Excluded(9), Bound::Included(60)),
+            (Bound::Unbounded, Bound::Excluded(0)), (Bound::Included(200), Bound::Unbounded), (Bound::Unbounded, Bound::Unbounded)];
+        for range in ranges {
+            let ranks = tree.rank_range(range);
+            assert_eq!(tree.iter().filter(|(key, _)| range.contains(*key)).count(), ranks.len());
+            if let Some((first, _)) = tree.range(range).next() {
+                assert_eq!(Some(ranks.start), tree.iter().position(|(key, _)| key == first));
+            }
+        }
+        assert_eq!(4..14, tree.rank_range(10..40));
+        assert_eq!(50..50, tree.rank_range(200..));
+        assert_eq!(2..2, tree.rank_range((Bound::Included(5), Bound::Excluded(4))));
+    }
+
+    #[test]
+    fn test_bounded_insert_rejects_keys_outside_domain() {
+        let mut tree = RedBlack::new();
+        assert_eq!(Ok(None), tree.bounded_insert(5, 'a', &1, &10));
+        assert_eq!(Ok(Some('a')), tree.bounded_insert(5, 'b', &1, &10));
+        assert_eq!(Ok(None), tree.bounded_insert(1, 'c', &1, &10));
+        assert_eq!(Ok(None), tree.bounded_insert(10, 'd', &1, &10));
+        assert_eq!(Err((0, 'e')), tree.bounded_insert(0, 'e', &1, &10));
+        assert_eq!(Err((11, 'f')), tree.bounded_insert(11, 'f', &1, &10));
+        assert_eq!(vec![(&1, &'c'), (&5, &'b'), (&10, &'d')], tree.iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_search_path_values_follow_descent() {
+        let mut tree = AVL::new();
+        for key in 0..31 {
+            tree.insert(key * 2, key * 20);
+        }
+        let path: Vec<i32> = tree.path_to(&14).unwrap().into_iter().map(|key| key * 10).collect();
+        assert_eq!(path.iter().collect::<Vec<_>>(), tree.search_path_values(&14));
+        // 31 nodes form perfect tree, so missing key ends at a leaf in depth 4
+        let values = tree.search_path_values(&15);
+        assert_eq!(5, values.len());
+        assert!(values.ends_with(&[&140]) || values.ends_with(&[&160]));
+        assert!(AVL::<i32, i32>::new().search_path_values(&1).is_empty());
+    }
+
+    #[test]
+    fn test_reversed_iterates_in_descending_order() {
+        let mut tree = AVL::new();
+        for key in 0..50 {
+            tree.insert(key, key * 10);
+        }
+        let reversed = tree.reversed();
+        assert_eq!((0..50).rev().collect::<Vec<_>>(), reversed.iter().map(|(Reverse(key), _)| *key).collect::<Vec<_>>());
+        assert_eq!(Some(&70), reversed.find(&Reverse(7)));
+        assert!(reversed.is_valid());
+        assert_eq!(50, tree.len());
+        assert!(RedBlack::<u8, ()>::new().reversed().is_empty());
+    }
+
+    #[test]
+    fn test_retain_intersection_keeps_shared_keys() {
+        let mut tree = RedBlack::new();
+        let mut other = RedBlack::new();
+        for key in 0..100 {
+            tree.insert(key, 'a');
+            other.insert(key + 60, 'b');
+        }
+        tree.retain_intersection(&other);
+        assert_eq!((60..100).collect::<Vec<_>>(), tree.iter().map(|(key, _)| *key).collect::<Vec<_>>());
+        // values of this tree are kept
+        assert!(tree.iter().all(|(_, value)| *value == 'a'));
+        assert!(tree.is_valid());
+
+        tree.retain_intersection(&RedBlack::new());
+        assert!(tree.is_empty());
+    }
+
+    #[test]
+    fn test_increment_and_decrement_count_words() {
+        let words = "a b a c b a".split(' ');
+        let mut counts: AVL<&str, i32> = AVL::new();
+        for word in words.clone() {
+            counts.increment(word);
+        }
+        assert_eq!(vec![(&"a", &3), (&"b", &2), (&"c", &1)], counts.iter().collect::<Vec<_>>());
+        for word in words {
+            counts.decrement(word);
+        }
+        assert!(counts.is_empty());
+
+        let mut balance: RedBlack<char, i64> = RedBlack::new();
+        balance.decrement('x');
+        assert_eq!(Some(&-1), balance.find(&'x'));
+        balance.increment('x');
+        // increment does not remove entries reaching zero
+        assert_eq!(Some(&0), balance.find(&'x'));
+    }
+
+    #[test]
+    fn test_map_keys_monotonic_keeps_shape() {
+        let mut tree = RedBlack::new();
+        for key in 0..50i64 {
+            tree.insert(key, key);
+        }
+        let depths: Vec<(usize, i64)> = tree.iter_with_depth().map(|(depth, key, _)| (depth, *key)).collect();
+        let shifted = tree.map_keys_monotonic(|key| key + 1000);
+        assert!(shifted.is_valid());
+        let shifted_depths: Vec<(usize, i64)> = shifted.iter_with_depth().map(|(depth, key, _)| (depth, key - 1000)).collect();
+        assert_eq!(depths, shifted_depths);
+        assert_eq!(Some(&7), shifted.find(&1007));
+
+        let scaled = shifted.map_keys_monotonic(|key| format!("{:05}", key * 3));
+        assert!(scaled.is_valid());
+        assert_eq!(Some((&"03000".to_string(), &0)), scaled.min());
+    }
+
+    #[test]
+    fn test_is_valid_detects_unordered_keys_and_wrong_sizes() {
+        let mut tree = AVL::new();
+        for key in 0..20 {
+            tree.insert(key, ());
+        }
+        assert!(tree.is_valid());
+        tree.root.as_mut().unwrap().size += 1;
+        assert!(!tree.is_valid());
+        tree.root.as_mut().unwrap().size -= 1;
+        tree.root.as_mut().unwrap().key = 100;
+        assert!(!tree.is_valid());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "BST order")]
+    fn test_assert_invariants_names_bst_order() {
+        let mut tree = RedBlack::new();
+        for key in 0..20 {
+            tree.insert(key, ());
+        }
+        tree.assert_invariants();
+        tree.root.as_mut().unwrap().key = 100;
+        tree.assert_invariants();
+    }
+
+    #[test]
+    fn test_iter_chunks_groups_sorted_entries() {
+        let mut tree = AVL::new();
+        for key in 0..10u32 {
+            tree.insert(key, ());
+        }
+        let chunks: Vec<Vec<u32>> = tree.iter_chunks(3)
+            .map(|chunk| chunk.into_iter().map(|(key, _)| *key).collect())
+            .collect();
+        assert_eq!(vec![vec![0, 1, 2], vec![3, 4, 5], vec![6, 7, 8], vec![9]], chunks);
+        assert_eq!(1, tree.iter_chunks(10).count());
+        assert_eq!(0, AVL::<u32, ()>::new().iter_chunks(3).count());
+    }
+
+    #[test]
+    fn test_take_range_cuts_out_window() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for key in 0..100 {
+            avl.insert(key, key * 2);
+            rb.insert(key, key * 2);
+        }
+        let expected: Vec<(i32, i32)> = (20..40).map(|key| (key, key * 2)).collect();
+        assert_eq!(expected, avl.take_range(20..40));
+        assert_eq!(expected, rb.take_range(20..40));
+        assert_eq!(80, avl.len());
+        assert!(avl.iter().all(|(key, _)| !(20..40).contains(key)));
+        assert!(avl.iter().eq(rb.iter()));
+        rb.insert(30, 0);
+        assert_eq!(Some((50, 100)), rb.remove(&50));
+        assert_eq!(80, rb.node_count());
+
+        assert!(avl.take_range(20..40).is_empty());
+        assert!(avl.take_range((Bound::Included(60), Bound::Excluded(50))).is_empty());
+        assert_eq!(80, avl.take_range(..).len());
+    }
+
+    #[test]
+    fn test_min_and_max_by_value() {
+        // values are ordered in reverse to keys, 1 and 3 share the largest value
+        let entries = [(1, 9), (2, 5), (3, 9), (4, 2), (5, 7)];
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for (key, value) in entries {
+            avl.insert(key, value);
+            rb.insert(key, value);
+        }
+
+        assert_eq!(Some((&4, &2)), avl.min_by_value());
+        assert_eq!(Some((&4, &2)), rb.min_by_value());
+        assert_eq!(Some((&1, &9)), avl.max_by_value());
+        assert_eq!(Some((&1, &9)), rb.max_by_value());
+        assert_eq!(None, AVL::<u32, u32>::new().min_by_value());
+        assert_eq!(None, RedBlack::<u32, u32>::new().max_by_value());
+    }
+
+    #[test]
+    fn test_inorder_keys_are_sorted() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for i in 0..1000 {
+            avl.insert((i * 7919) % 1000, i);
+            rb.insert((i * 7919) % 1000, i);
+        }
+
+        let expected: Vec<i32> = (0..1000).collect();
+        assert_eq!(expected.iter().collect::<Vec<&i32>>(), avl.inorder_keys());
+        assert_eq!(expected.iter().collect::<Vec<&i32>>(), rb.inorder_keys());
+    }
+
+    #[test]
+    fn test_range_values_mut_alters_only_range() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for i in 0..100 {
+            avl.insert(i, i);
+            rb.insert(i, i);
+        }
+
+        avl.range_values_mut(20..80).for_each(|value| *value *= 2);
+        rb.range_values_mut(20..80).for_each(|value| *value *= 2);
+        for i in 0..100 {
+            let expected = if (20..80).contains(&i) { i * 2 } else { i };
+            assert_eq!(Some(&expected), avl.find(&i));
+            assert_eq!(Some(&expected), rb.find(&i));
+        }
+
+        assert_eq!(vec![&98, &99], rb.range_mut(97..).map(|(key, _)| key).skip(1).collect::<Vec<&i32>>());
+        assert_eq!(11, avl.range_mut(..=10).count());
+        assert_eq!(0, avl.range_mut(50..50).count());
+        assert_eq!(0, avl.range_mut(200..).count());
+    }
+
+    #[test]
+    fn test_get2_mut() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for i in 0..100 {
+            avl.insert(i, 100);
+            rb.insert(i, 100);
+        }
+
+        for (first, second) in [(0, 99), (99, 0), (10, 11), (50, 49), (avl.root.as_ref().unwrap().key, 7)] {
+            if let (Some(from), Some(to)) = avl.get2_mut(&first, &second) {
+                *from -= 10;
+                *to += 10;
+            }
+            assert_eq!(Some(&90), avl.find(&first));
+            assert_eq!(Some(&110), avl.find(&second));
+            avl.insert(first, 100);
+            avl.insert(second, 100);
+        }
+
+        let (from, to) = rb.get2_mut(&5, &150);
+        assert_eq!(Some(&mut 100), from);
+        assert_eq!(None, to);
+        assert_eq!((None, None), rb.get2_mut(&-1, &150));
+    }
+
+    #[test]
+    #[should_panic(expected = "keys must be different")]
+    fn test_get2_mut_rejects_same_keys() {
+        let mut tree = AVL::new();
+        tree.insert(1, 1);
+        tree.get2_mut(&1, &1);
+    }
+
+    #[test]
+    fn test_any_and_all_short_circuit() {
+        let (avl, rb) = both_trees((0..100).map(|i| (i, i)));
+
+        let mut visited = 0;
+        assert!(avl.any(|key, _| {
+            visited += 1;
+            *key == 10
+        }));
+        assert_eq!(11, visited);
+
+        visited = 0;
+        assert!(!rb.all(|_, value| {
+            visited += 1;
+            *value < 20
+        }));
+        assert_eq!(21, visited);
+
+        assert!(avl.all(|key, value| key == value));
+        assert!(!rb.any(|key, _| *key >= 100));
+        assert!(!AVL::<i32, i32>::new().any(|_, _| true));
+        assert!(RedBlack::<i32, i32>::new().all(|_, _| false));
+    }
+
+    #[test]
+    fn test_try_insert_rejects_duplicates() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for i in 0..100 {
+            *avl.try_insert(i, i).unwrap() += 1;
+            *rb.try_insert(i, i).unwrap() += 1;
+        }
+
+        for i in 0..100 {
+            assert_eq!(Err((i, 0)), avl.try_insert(i, 0));
+            assert_eq!(Err((i, 0)), rb.try_insert(i, 0));
+            assert_eq!(Some(&(i + 1)), avl.find(&i));
+            assert_eq!(Some(&(i + 1)), rb.find(&i));
+        }
+        assert_eq!(100, avl.len());
+    }
+
+    #[test]
+    fn test_zip_matched_yields_common_keys() {
+        let mut multiples_of_two = AVL::new();
+        let mut multiples_of_three = RedBlack::new();
+        for i in 0..50 {
+            multiples_of_two.insert(i * 2, i);
+            multiples_of_three.insert(i * 3, format!("{i}"));
+        }
+
+        let matched: Vec<(i32, i32, String)> = multiples_of_two.zip_matched(&multiples_of_three)
+            .map(|(key, value, other)| (*key, *value, other.clone()))
+            .collect();
+        let expected: Vec<(i32, i32, String)> = (0..17).map(|i| (i * 6, i * 3, format!("{}", i * 2))).collect();
+        assert_eq!(expected, matched);
+        assert_eq!(0, multiples_of_two.zip_matched(&RedBlack::<i32, i32>::new()).count());
+    }
+
+    #[test]
+    fn test_values_aggregates() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for i in 0..100 {
+            avl.insert(i, 99 - i);
+            rb.insert(i, 99 - i);
+        }
+
+        assert_eq!(4950, avl.values_sum());
+        assert_eq!(4950, rb.values_sum());
+        assert_eq!(Some(99), avl.values_max());
+        assert_eq!(Some(0), rb.values_min());
+        assert_eq!(0, AVL::<u32, u32>::new().values_sum());
+        assert_eq!(None, RedBlack::<u32, u32>::new().values_max());
+    }
+
+    #[test]
+    fn test_thawed_tree_can_be_altered() {
+        let mut tree = AVL::new();
+        tree.insert(1, 1);
+        tree.freeze();
+        assert!(tree.is_frozen());
+        assert_eq!(Some(&1), tree.find(&1));
+        tree.thaw();
+        tree.insert(2, 2);
+        assert_eq!(Some((2, 2)), tree.remove(&2));
+    }
+
+    #[test]
+    #[should_panic(expected = "frozen")]
+    fn test_frozen_tree_rejects_insert() {
+        let mut tree = AVL::new();
+        tree.freeze();
+        tree.insert(1, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "frozen")]
+    fn test_frozen_tree_rejects_remove() {
+        let mut tree = RedBlack::new();
+        tree.insert(1, 1);
+        tree.freeze();
+        tree.remove(&1);
+    }
+
+    #[test]
+    #[should_panic(expected = "frozen")]
+    fn test_frozen_tree_rejects_shrink_to_balanced() {
+        let mut tree = AVL::new();
+        tree.insert(1, 1);
+        tree.freeze();
+        tree.shrink_to_balanced();
+    }
+
+    #[test]
+    #[should_panic(expected = "frozen")]
+    fn test_frozen_tree_rejects_bounded_insert() {
+        let mut tree = RedBlack::new();
+        tree.insert(1, 1);
+        tree.freeze();
+        let _ = tree.bounded_insert(1, 2, &0, &10);
+    }
+
+    #[test]
+    #[should_panic(expected = "frozen")]
+    fn test_frozen_tree_rejects_batch_apply() {
+        let mut tree = AVL::new();
+        tree.insert(1, 1);
+        tree.freeze();
+        tree.batch_apply([(1, 2)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "frozen")]
+    fn test_frozen_tree_rejects_increment() {
+        let mut tree: AVL<char, i32> = AVL::new();
+        tree.freeze();
+        tree.increment('a');
+    }
+
+    #[test]
+    #[should_panic(expected = "frozen")]
+    fn test_frozen_tree_rejects_decrement() {
+        let mut tree: RedBlack<char, i32> = RedBlack::new();
+        tree.insert('a', 2);
+        tree.freeze();
+        tree.decrement('a');
+    }
+
+    #[test]
+    fn test_paging_covers_all_entries() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for i in 0..100 {
+            avl.insert(i, i * 2);
+            rb.insert(i, i * 2);
+        }
+
+        for pages in [page_all(&avl), page_all(&rb)] {
+            assert_eq!(10, pages.len());
+            let keys: Vec<i32> = pages.iter().flatten().map(|(key, _)| **key).collect();
+            assert_eq!((0..100).collect::<Vec<i32>>(), keys);
+        }
+
+        assert_eq!(vec![(&98, &196), (&99, &198)], avl.page(Some(&97), 10).entries);
+        assert_eq!(None, avl.page(Some(&97), 10).next_after);
+        assert!(avl.page(Some(&99), 10).entries.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "page limit must be positive")]
+    fn test_page_rejects_zero_limit() {
+        let mut tree = AVL::new();
+        tree.insert(1, 1);
+        tree.page(None, 0);
+    }
+
+    #[test]
+    fn test_iter_from_starts_at_ceiling() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for i in (0..50).map(|i| i * 2) {
+            avl.insert(i, i);
+            rb.insert(i, i);
+        }
+
+        let expected: Vec<i32> = (21..50).map(|i| i * 2).collect();
+        assert_eq!(expected, avl.iter_from(&41).map(|(key, _)| *key).collect::<Vec<i32>>());
+        assert_eq!(expected, rb.iter_from(&41).map(|(key, _)| *key).collect::<Vec<i32>>());
+        assert_eq!(expected, avl.iter_from(&42).map(|(key, _)| *key).collect::<Vec<i32>>());
+        assert_eq!(50, avl.iter_from(&-1).count());
+        assert_eq!(None, rb.iter_from(&99).next());
+    }
+
+    #[test]
+    fn test_path_to_starts_at_root() {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for i in 0..100 {
+            avl.insert(i, i);
+            rb.insert(i, i);
+        }
+
+        for key in 0..100 {
+            let path = avl.path_to(&key).unwrap();
+            assert_eq!(avl.depth_of(&key).unwrap() + 1, path.len());
+            assert_eq!(avl.root.as_ref().unwrap().key, *path[0]);
+            assert_eq!(key, *path[path.len() - 1]);
+
+            let path = rb.path_to(&key).unwrap();
+            assert_eq!(rb.depth_of(&key).unwrap() + 1, path.len());
+            assert_eq!(rb.root.as_ref().unwrap().key, *path[0]);
+        }
+        assert_eq!(None, avl.path_to(&100));
+        assert_eq!(None, RedBlack::<i32, i32>::new().depth_of(&0));
+    }
+
+    #[test]
+    fn test_structural_rotations_ignore_metadata() {
+        // same subtree with different metadata
+        //        4
+        //       / \
+        //      2   6
+        //     / \ / \
+        //    1  3 5  7
+        let shape = [(4, 0), (2, 1), (1, 2), (3, 2), (6, 1), (5, 2), (7, 2)];
+        let mut factors = build_subtree(&shape, |depth| depth as i8);
+        let mut colors = build_subtree(&shape, |depth| if depth == 1 { Color::Red } else { Color::Black });
+
+        factors.rotate_left();
+        colors.rotate_left();
+        // metadata are 1 for nodes originally in depth 1
+        let expected = vec![(6, 7, 1), (4, 5, 0), (2, 3, 1), (1, 1, 0), (3, 1, 0), (5, 1, 0), (7, 1, 0)];
+        assert_eq!(expected, preorder(&factors, &|factor| (*factor == 1) as usize));
+        assert_eq!(expected, preorder(&colors, &|color| (*color == Color::Red) as usize));
+
+        factors.rotate_right();
+        colors.rotate_right();
+        factors.rotate_from(super::Side::Left);
+        colors.rotate_from(super::Side::Left);
+        let expected = vec![(2, 7, 1), (1, 1, 0), (4, 5, 0), (3, 1, 0), (6, 3, 1), (5, 1, 0), (7, 1, 0)];
+        assert_eq!(expected, preorder(&factors, &|factor| (*factor == 1) as usize));
+        assert_eq!(expected, preorder(&colors, &|color| (*color == Color::Red) as usize));
+    }
+
+    // builds subtree from (key, depth) in preorder, metadata are derived from depth
+    fn build_subtree<M>(nodes: &[(u32, usize)], metadata: impl Fn(usize) -> M + Copy) -> Box<Node<u32, (), M>> {
+        let (key, depth) = nodes[0];
+        let children = &nodes[1..];
+        let right_start = children.iter().skip(1).position(|(_, child_depth)| *child_depth == depth + 1)
+            .map_or(children.len(), |position| position + 1);
+        let subtree = |nodes: &[(u32, usize)]| -> Child<u32, (), M> {
+            if nodes.is_empty() { None } else { Some(build_subtree(nodes, metadata)) }
+        };
+        let mut node = Box::new(Node {
+            key,
+            value: (),
+            left_child: subtree(&children[..right_start]),
+            right_child: subtree(&children[right_start..]),
+            size: 1,
+            metadata: metadata(depth),
+        });
+        node.update_size();
+        node
+    }
+
+    // (key, subtree size, metadata) in preorder
+    fn preorder<M>(node: &Node<u32, (), M>, metadata: &impl Fn(&M) -> usize) -> Vec<(u32, usize, usize)> {
+        let mut nodes = vec![(node.key, node.size, metadata(&node.metadata))];
+        for child in [&node.left_child, &node.right_child].into_iter().flatten() {
+            nodes.extend(preorder(child, metadata));
+        }
+        nodes
+    }
+
+    fn page_all<I>(tree: &Tree<i32, i32, I>) -> Vec<Vec<(&i32, &i32)>> {
+        let mut pages = Vec::new();
+        let mut after = None;
+        loop {
+            let page = tree.page(after, 10);
+            assert!(page.entries.len() <= 10);
+            pages.push(page.entries);
+            match page.next_after {
+                None => return pages,
+                Some(key) => after = Some(key),
+            }
+        }
+    }
+
+    // same entries inserted into trees of both balancing strategies
+    fn both_trees<K: Ord + Clone, V: Clone>(entries: impl IntoIterator<Item=(K, V)>) -> (AVL<K, V>, RedBlack<K, V>) {
+        let mut avl = AVL::new();
+        let mut rb = RedBlack::new();
+        for (key, value) in entries {
+            avl.insert(key.clone(), value.clone());
+            rb.insert(key, value);
+        }
+        (avl, rb)
+    }
+}
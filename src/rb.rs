@@ -1,7 +1,10 @@
 use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt;
 use std::fmt::Debug;
 use std::mem;
-use super::Side;
+use super::{Balancing, Side, TreeStats};
+use super::avl::AVL;
 
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -10,6 +13,47 @@ pub enum Color {
     Red,
 }
 
+// Description of exact tree shape for RedBlack::from_parts
+pub struct NodeDescription<K, V> {
+    pub key: K,
+    pub value: V,
+    pub color: Color,
+    pub left: Option<Box<NodeDescription<K, V>>>,
+    pub right: Option<Box<NodeDescription<K, V>>>,
+}
+
+impl<K, V> NodeDescription<K, V> {
+    pub fn new(key: K, value: V, color: Color, left: Option<Self>, right: Option<Self>) -> Self {
+        NodeDescription { key, value, color, left: left.map(Box::new), right: right.map(Box::new) }
+    }
+
+    pub fn leaf(key: K, value: V, color: Color) -> Self {
+        Self::new(key, value, color, None, None)
+    }
+}
+
+// Red-black rule broken by described tree
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Violation {
+    RedRoot,
+    RedChildOfRed,
+    BlackHeightMismatch,
+    UnorderedKeys,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::RedRoot => write!(f, "root must be black"),
+            Violation::RedChildOfRed => write!(f, "red node must not have red child"),
+            Violation::BlackHeightMismatch => write!(f, "all paths to leafs must contain same number of black nodes"),
+            Violation::UnorderedKeys => write!(f, "keys must be strictly increasing in inorder succession"),
+        }
+    }
+}
+
+impl Error for Violation {}
+
 type InsertRotation = Option<Side>;
 type Node<K, V> = super::Node<K, V, Color>;
 type BoxedNode<K, V> = Box<Node<K, V>>;
@@ -17,6 +61,74 @@ type Child<K, V> = super::Child<K, V, Color>;
 pub type RedBlack<K, V> = super::Tree<K, V, Color>;
 
 impl<K: Ord, V> Node<K, V> {
+    // builds described subtree, returns it with its black height
+    fn from_description(description: NodeDescription<K, V>) -> Result<(BoxedNode<K, V>, usize), Violation> {
+        let NodeDescription { key, value, color, left, right } = description;
+        let build_child = |child: Option<Box<NodeDescription<K, V>>>| -> Result<(Child<K, V>, usize), Violation> {
+            match child {
+                None => Ok((None, 0)),
+                Some(child) => {
+                    let (child, black_height) = Self::from_description(*child)?;
+                    if color == Color::Red && child.metadata == Color::Red {
+                        return Err(Violation::RedChildOfRed);
+                    }
+                    Ok((Some(child), black_height))
+                }
+            }
+        };
+        let (left_child, left_height) = build_child(left)?;
+        let (right_child, right_height) = build_child(right)?;
+        if left_height != right_height {
+            return Err(Violation::BlackHeightMismatch);
+        }
+        let mut node = Box::new(Node { key, value, left_child, right_child, size: 1, metadata: color });
+        node.update_size();
+        Ok((node, left_height + usize::from(color == Color::Black)))
+    }
+
+    // checks red-black rules in subtree of red or black parent, returns its black height
+    fn checked_black_height(node: &Child<K, V>, parent_color: Color) -> Result<usize, Violation> {
+        let Some(node) = node else {
+            return Ok(0);
+        };
+        if parent_color == Color::Red && node.metadata == Color::Red {
+            return Err(Violation::RedChildOfRed);
+        }
+        let left = Self::checked_black_height(&node.left_child, node.metadata)?;
+        let right = Self::checked_black_height(&node.right_child, node.metadata)?;
+        if left != right {
+            return Err(Violation::BlackHeightMismatch);
+        }
+        Ok(left + usize::from(node.metadata == Color::Black))
+    }
+
+    // numbers of red and black nodes in subtree
+    fn color_counts(node: &Child<K, V>) -> (usize, usize) {
+        let Some(node) = node else {
+            return (0, 0);
+        };
+        let (left_red, left_black) = Self::color_counts(&node.left_child);
+        let (right_red, right_black) = Self::color_counts(&node.right_child);
+        let (red, black) = (left_red + right_red, left_black + right_black);
+        match node.metadata {
+            Color::Red => (red + 1, black),
+            Color::Black => (red, black + 1),
+        }
+    }
+
+    // number of black nodes on path from this node to a leaf, same for all paths in valid tree
+    fn black_height(&self) -> usize {
+        let mut node = Some(self);
+        let mut height = 0;
+        while let Some(current) = node {
+            if current.metadata == Color::Black {
+                height += 1;
+            }
+            node = current.left_child.as_deref();
+        }
+        height
+    }
+
     fn insert(&mut self, key: K, value: V) {
         self.insert_recursively(key, value);
         // after recursive insertion we can get red root and red children, we can fix this with painting root black
@@ -35,25 +147,29 @@ impl<K: Ord, V> Node<K, V> {
                 match &mut self.left_child {
                     None => {
                         self.left_child = new_node(key, value, Color::Red);
+                        self.update_size();
                         return self.resolve_rotation(Color::Red, Side::Left);
                     }
                     Some(child) => {
                         rotation = child.insert_recursively(key, value);
                     }
                 };
-                return self.handle_insert_rotation(rotation, Side::Left);
+                self.update_size();
+                self.handle_insert_rotation(rotation, Side::Left)
             }
             Ordering::Greater => {
                 match &mut self.right_child {
                     None => {
                         self.right_child = new_node(key, value, Color::Red);
+                        self.update_size();
                         return self.resolve_rotation(Color::Red, Side::Right);
                     }
                     Some(child) => {
                         rotation = child.insert_recursively(key, value);
                     }
                 }
-                return self.handle_insert_rotation(rotation, Side::Right);
+                self.update_size();
+                self.handle_insert_rotation(rotation, Side::Right)
             }
         }
     }
@@ -68,7 +184,8 @@ impl<K: Ord, V> Node<K, V> {
                         child.metadata = Color::Black;
                     }
                     self.metadata = Color::Red;
-                    self.resolve_rotation(Color::Red, child_side)
+                    // self may now violate rules with its parent, which is checked one level higher
+                    None
                 } else {
                     self.rotate(child_side, grand_child_side);
                     None
@@ -78,7 +195,9 @@ impl<K: Ord, V> Node<K, V> {
                 None
             }
         } else {
-            None
+            // child may have been painted red by recoloring below it
+            let child_color = self.child(child_side).as_ref().map_or(Color::Black, |child| child.metadata);
+            self.resolve_rotation(child_color, child_side)
         }
     }
 
@@ -112,51 +231,11 @@ impl<K: Ord, V> Node<K, V> {
         }
     }
 
-    fn rotate_from(&mut self, side: Side) {
-        match side {
-            Side::Left => self.rotate_right(),
-            Side::Right => self.rotate_left()
-        }
-    }
-
-    fn rotate_to(&mut self, side: Side) {
-        match side {
-            Side::Left => self.rotate_left(),
-            Side::Right => self.rotate_right()
-        }
-    }
-
-
-    // Rotates tree to the left
-    //        a                b
-    //       / \              / \
-    //      W   b            a   Y
-    //         / \    =>    / \
-    //        Z   Y        W   Z
-    fn rotate_left(&mut self) {
-        let mut new_self = self.right_child.take().unwrap(); // takes b
-        self.right_child = new_self.left_child.take(); // reassign Z
-        mem::swap(self, &mut new_self);
-        self.left_child = Some(new_self); // takes a to b
-    }
-
-    // Rotates tree to the right
-    //        a            b
-    //       / \          / \
-    //      b   X        Z   a
-    //     / \      =>      / \
-    //    Z   Y            Y   X
-    fn rotate_right(&mut self) {
-        let mut new_self = self.left_child.take().unwrap(); // takes b
-        self.left_child = new_self.right_child.take(); // reassign Y
-        mem::swap(self, &mut new_self);
-        self.right_child = Some(new_self); // takes a to b
-    }
-
-    fn pop_smallest_node(mut node: BoxedNode<K, V>) -> (Child<K, V>, BoxedNode<K, V>, bool) {
-        match node.left_child.take() {
+    // pops the smallest node for left side and the largest node for right side
+    fn pop_outermost_node(mut node: BoxedNode<K, V>, side: Side) -> (Child<K, V>, BoxedNode<K, V>, bool) {
+        match node.child(side).take() {
             None => {
-                match node.right_child.take() {
+                match node.another_child(side).take() {
                     None => {
                         match node.metadata {
                             Color::Red => {
@@ -167,45 +246,51 @@ impl<K: Ord, V> Node<K, V> {
                             }
                         }
                     }
-                    Some(mut right_child) => {
-                        right_child.metadata = Color::Black;
-                        (Some(right_child), node, false)
+                    Some(mut inner_child) => {
+                        inner_child.metadata = Color::Black;
+                        (Some(inner_child), node, false)
                     }
                 }
             }
             Some(child) => {
-                let (left, popped, mut check_needed) = Self::pop_smallest_node(child);
-                node.left_child = left;
+                let (outer, popped, mut check_needed) = Self::pop_outermost_node(child, side);
+                *node.child(side) = outer;
+                node.update_size();
                 if check_needed {
-                    check_needed = node.check_imbalance_after_delete(Side::Left);
+                    check_needed = node.check_imbalance_after_delete(side);
                 }
                 (Some(node), popped, check_needed)
             }
         }
     }
 
-    fn remove(self, value: &K) -> (Child<K, V>, Option<(K, V)>) {
-        let (mut node, removed, _) = self.remove_recursively(value);
+    fn remove<F: FnOnce(&V) -> bool>(self, value: &K, predicate: F) -> (Child<K, V>, Option<(K, V)>) {
+        let (mut node, removed, _) = self.remove_recursively(value, predicate);
         if let Some(node) = node.as_mut() {
             // after recursive insertion we can get red root and red children, we can fix this with painting root black
             node.metadata = Color::Black;
         }
 
-        return (node, removed);
+        (node, removed)
     }
 
-    fn remove_recursively(mut self, key: &K) -> (Child<K, V>, Option<(K, V)>, bool) {
+    // removes node with given key, if its value satisfies predicate
+    fn remove_recursively<F: FnOnce(&V) -> bool>(mut self, key: &K, predicate: F) -> (Child<K, V>, Option<(K, V)>, bool) {
         match key.cmp(&self.key) {
             Ordering::Equal => {
+                if !predicate(&self.value) {
+                    return (Some(Box::new(self)), None, false);
+                }
                 let (has_left_child, has_right_child) = (self.left_child.is_some(), self.right_child.is_some());
                 if has_right_child && has_left_child {
-                    let (right, mut replacement, mut check_needed) = Self::pop_smallest_node(self.right_child.take().unwrap());
+                    let (right, mut replacement, mut check_needed) = Self::pop_outermost_node(self.right_child.take().unwrap(), Side::Left);
 
                     // replace self with next node in inorder succession
                     mem::swap(&mut replacement.key, &mut self.key);
                     mem::swap(&mut replacement.value, &mut self.value);
 
                     self.right_child = right;
+                    self.update_size();
                     if check_needed {
                         check_needed = self.check_imbalance_after_delete(Side::Right);
                     }
@@ -241,8 +326,9 @@ impl<K: Ord, V> Node<K, V> {
             Ordering::Greater => {
                 match self.right_child.take() {
                     Some(child) => {
-                        let (child, value, mut check_needed) = child.remove_recursively(key);
+                        let (child, value, mut check_needed) = child.remove_recursively(key, predicate);
                         self.right_child = child;
+                        self.update_size();
                         if check_needed {
                             check_needed = self.check_imbalance_after_delete(Side::Right);
                         }
@@ -256,8 +342,9 @@ impl<K: Ord, V> Node<K, V> {
             Ordering::Less => {
                 match self.left_child.take() {
                     Some(child) => {
-                        let (child, value, mut check_needed) = child.remove_recursively(key);
+                        let (child, value, mut check_needed) = child.remove_recursively(key, predicate);
                         self.left_child = child;
+                        self.update_size();
                         if check_needed {
                             check_needed = self.check_imbalance_after_delete(Side::Left);
                         }
@@ -372,27 +459,35 @@ fn new_node<K: Ord, V>(key: K, value: V, color: Color) -> Child<K, V> {
         value,
         left_child: None,
         right_child: None,
+        size: 1,
         metadata: color,
     }))
 }
 
 impl<K: Ord, V> RedBlack<K, V> {
     pub fn new() -> Self {
-        return RedBlack { root: None };
+        Self::new_empty()
     }
 
     pub fn remove(&mut self, value: &K) -> Option<(K, V)> {
+        self.remove_if(value, |_| true)
+    }
+
+    /// Removes node with given key only if its value satisfies predicate.
+    pub fn remove_if<F: FnOnce(&V) -> bool>(&mut self, key: &K, predicate: F) -> Option<(K, V)> {
+        self.assert_not_frozen();
         match self.root.take() {
             None => { None }
             Some(node) => {
                 let returned_value;
-                (self.root, returned_value) = node.remove(value);
+                (self.root, returned_value) = node.remove(key, predicate);
                 returned_value
             }
         }
     }
 
     pub fn insert(&mut self, key: K, value: V) {
+        self.assert_not_frozen();
         match &mut self.root {
             None => {
                 self.root = new_node(key, value, Color::Black);
@@ -402,44 +497,334 @@ impl<K: Ord, V> RedBlack<K, V> {
             }
         }
     }
+
+    /// Builds exactly described tree, mainly for tests of specific rebalancing scenarios.
+    /// Returns error if the description breaks rules of red-black tree.
+    pub fn from_parts(root: NodeDescription<K, V>) -> Result<Self, Violation> {
+        if root.color == Color::Red {
+            return Err(Violation::RedRoot);
+        }
+        let mut tree = Self::new();
+        tree.root = Some(Node::from_description(root)?.0);
+        if !tree.is_bst() {
+            return Err(Violation::UnorderedKeys);
+        }
+        Ok(tree)
+    }
+
+    fn pop_outermost(&mut self, side: Side) -> Option<(K, V)> {
+        self.assert_not_frozen();
+        let (mut root, popped, _) = Node::pop_outermost_node(self.root.take()?, side);
+        if let Some(root) = root.as_mut() {
+            root.metadata = Color::Black;
+        }
+        self.root = root;
+        Some((popped.key, popped.value))
+    }
+
+    /// Converts the tree into balanced AVL tree with same entries in linear time.
+    pub fn into_avl(self) -> AVL<K, V> {
+        self.into_strategy()
+    }
+
+    /// Returns diagnostics of the tree, balance is number of black nodes on any path from the root to a leaf.
+    pub fn stats(&self) -> TreeStats<K, usize>
+    where
+        K: Clone,
+    {
+        self.stats_with(self.root.as_ref().map_or(0, |node| node.black_height()))
+    }
+
+    /// Returns color of node with given key.
+    pub fn color_of(&self, key: &K) -> Option<Color> {
+        self.metadata_of(key).copied()
+    }
+
+    /// Returns number of red and black nodes, in this order.
+    pub fn color_counts(&self) -> (usize, usize) {
+        Node::color_counts(&self.root)
+    }
 }
 
 
+impl<K: Ord, V> Balancing<K, V> for Color {
+    fn insert(tree: &mut RedBlack<K, V>, key: K, value: V) {
+        tree.insert(key, value)
+    }
+
+    fn remove(tree: &mut RedBlack<K, V>, key: &K) -> Option<(K, V)> {
+        tree.remove(key)
+    }
+
+    fn pop_first(tree: &mut RedBlack<K, V>) -> Option<(K, V)> {
+        tree.pop_outermost(Side::Left)
+    }
+
+    fn pop_last(tree: &mut RedBlack<K, V>) -> Option<(K, V)> {
+        tree.pop_outermost(Side::Right)
+    }
+
+    fn balanced_metadata(depth: usize, last_level: usize, _: usize, _: usize) -> Self {
+        // all paths to leafs contain same number of black nodes, if only nodes in the last level are red
+        if depth == last_level && depth > 0 {
+            Color::Red
+        } else {
+            Color::Black
+        }
+    }
+
+    fn check_invariants(tree: &RedBlack<K, V>) -> Result<(), String> {
+        if tree.root.as_ref().is_some_and(|root| root.metadata == Color::Red) {
+            return Err(Violation::RedRoot.to_string());
+        }
+        Node::checked_black_height(&tree.root, Color::Black).map(|_| ()).map_err(|violation| violation.to_string())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_conversion_to_avl_and_back() {
+        let mut tree = RedBlack::new();
+        for i in 0..300u32 {
+            tree.insert((i * 7919) % 1000, i);
+        }
+        let expected: Vec<(u32, u32)> = tree.iter().map(|(key, value)| (*key, *value)).collect();
+        let avl = tree.into_avl();
+        assert!(avl.iter().map(|(key, value)| (*key, *value)).eq(expected.iter().copied()));
+        let tree = avl.into_red_black();
+        check_tree(tree.root.as_ref().unwrap(), 300);
+        assert!(tree.iter().map(|(key, value)| (*key, *value)).eq(expected));
+    }
+
+    #[test]
+    fn test_stats_of_small_tree() {
+        let mut tree = RedBlack::new();
+        assert_eq!(TreeStats { len: 0, height: 0, min: None, max: None, balance: 0 }, tree.stats());
+        for key in [1, 2, 3, 4] {
+            tree.insert(key, ());
+        }
+        // black root 2 and black 1, 3 after recoloring caused by red 4
+        assert_eq!(TreeStats { len: 4, height: 2, min: Some(1), max: Some(4), balance: 2 }, tree.stats());
+    }
+
+    #[test]
+    fn test_from_parts_builds_exact_tree() {
+        use Color::{Black, Red};
+        // black 4 with black 2 (red children 1, 3) and black 6
+        let description = NodeDescription::new(4, 'd', Black,
+            Some(NodeDescription::new(2, 'b', Black,
+                Some(NodeDescription::leaf(1, 'a', Red)),
+                Some(NodeDescription::leaf(3, 'c', Red)))),
+            Some(NodeDescription::leaf(6, 'f', Black)));
+        let mut tree = RedBlack::from_parts(description).unwrap();
+        check_tree(tree.root.as_ref().unwrap(), 5);
+        assert_eq!(Some(vec![&4, &2, &3]), tree.path_to(&3));
+        assert_eq!(Some(Color::Red), tree.color_of(&1));
+
+        // removing black leaf 6 needs rotation with red nephew
+        assert_eq!(Some((6, 'f')), tree.remove(&6));
+        check_tree(tree.root.as_ref().unwrap(), 4);
+        assert_eq!(Some(&2), tree.path_to(&2).map(|path| path[0]));
+    }
+
+    #[test]
+    fn test_from_parts_rejects_broken_rules() {
+        use Color::{Black, Red};
+        assert_eq!(Some(Violation::RedRoot), RedBlack::from_parts(NodeDescription::leaf(1, (), Red)).err());
+        let red_red = NodeDescription::new(2, (), Black,
+            Some(NodeDescription::new(1, (), Red, Some(NodeDescription::leaf(0, (), Red)), None)), None);
+        assert_eq!(Some(Violation::RedChildOfRed), RedBlack::from_parts(red_red).err());
+        let unbalanced = NodeDescription::new(2, (), Black, Some(NodeDescription::leaf(1, (), Black)), None);
+        assert_eq!(Some(Violation::BlackHeightMismatch), RedBlack::from_parts(unbalanced).err());
+        let unordered = NodeDescription::new(2, (), Black,
+            Some(NodeDescription::leaf(3, (), Red)), Some(NodeDescription::leaf(1, (), Red)));
+        assert_eq!(Some(Violation::UnorderedKeys), RedBlack::from_parts(unordered).err());
+        let duplicate = NodeDescription::new(2, (), Black, Some(NodeDescription::leaf(2, (), Red)), None);
+        assert_eq!(Some(Violation::UnorderedKeys), RedBlack::from_parts(duplicate).err());
+    }
+
+    #[test]
+    fn test_is_valid_checks_red_black_rules() {
+        let mut tree = RedBlack::new();
+        for key in 0..100 {
+            tree.insert(key, ());
+            assert!(tree.is_valid());
+        }
+        tree.root.as_mut().unwrap().metadata = Color::Red;
+        assert!(!tree.is_valid());
+        tree.root.as_mut().unwrap().metadata = Color::Black;
+        tree.root.as_mut().unwrap().left_child.as_mut().unwrap().metadata = Color::Red;
+        assert!(!tree.is_valid());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "red node must not have red child")]
+    fn test_assert_invariants_names_color_rule() {
+        use Color::{Black, Red};
+        let description = NodeDescription::new(4, (), Black,
+            Some(NodeDescription::new(2, (), Black,
+                Some(NodeDescription::leaf(1, (), Red)),
+                Some(NodeDescription::leaf(3, (), Red)))),
+            Some(NodeDescription::leaf(6, (), Black)));
+        let mut tree = RedBlack::from_parts(description).unwrap();
+        tree.assert_invariants();
+        // red 2 gets red children 1 and 3
+        tree.root.as_mut().unwrap().left_child.as_mut().unwrap().metadata = Red;
+        tree.assert_invariants();
+    }
+
+    #[test]
+    fn test_color_counts_sum_to_len() {
+        let mut tree = RedBlack::new();
+        assert_eq!((0, 0), tree.color_counts());
+        tree.insert(1, ());
+        // the root is always black
+        assert_eq!((0, 1), tree.color_counts());
+        for key in [2, 3] {
+            tree.insert(key, ());
+        }
+        assert_eq!((2, 1), tree.color_counts());
+        for key in 4..500 {
+            tree.insert(key, ());
+            let (red, black) = tree.color_counts();
+            assert_eq!(tree.len(), red + black);
+            assert!(black >= tree.root.as_ref().unwrap().black_height());
+        }
+    }
+
+    #[test]
+    fn test_color_of_known_sequence() {
+        let mut tree = RedBlack::new();
+        for key in [1, 2, 3] {
+            tree.insert(key, ());
+        }
+        // rotation makes 2 black root with red children
+        assert_eq!(Some(Color::Black), tree.color_of(&2));
+        assert_eq!(Some(Color::Red), tree.color_of(&1));
+        assert_eq!(Some(Color::Red), tree.color_of(&3));
+
+        tree.insert(4, ());
+        // red uncle 1 causes recoloring, root stays black
+        assert_eq!(Some(Color::Black), tree.color_of(&2));
+        assert_eq!(Some(Color::Black), tree.color_of(&1));
+        assert_eq!(Some(Color::Black), tree.color_of(&3));
+        assert_eq!(Some(Color::Red), tree.color_of(&4));
+        assert_eq!(None, tree.color_of(&5));
+    }
+
     #[test]
     fn test_inserting_and_deleting_keeps_tree_balanced() {
         let vec: Vec<u32> = (0..1000).collect();
         let mut tree = RedBlack::new();
         for (i, j) in vec.iter().enumerate() {
-            tree.insert(j.clone(), j.clone());
+            tree.insert(*j, *j);
             check_tree(tree.root.as_ref().unwrap(), (i + 1) as u32);
         }
+        assert_eq!(vec.iter().collect::<Vec<&u32>>(), tree.inorder_keys());
         for j in vec.iter() {
             assert_eq!(Some(j), tree.find(j));
         }
         assert!(tree.root.is_some());
         let mut size = vec.len();
         for j in vec.iter() {
-            assert_eq!(Some((j.clone(), j.clone())), tree.remove(j));
+            assert_eq!(Some((*j, *j)), tree.remove(j));
             size -= 1;
             if size > 0 {
                 check_tree(tree.root.as_ref().unwrap(), size as u32);
+                assert_eq!(vec[vec.len() - size..].iter().collect::<Vec<&u32>>(), tree.inorder_keys());
             } else {
                 assert!(tree.root.is_none());
             }
         }
     }
 
-    fn check_tree<K: Ord, V>(tree: &Box<Node<K, V>>, expected_size: u32) {
+    #[test]
+    fn test_remove_if_removes_only_matching_values() {
+        let mut tree = RedBlack::new();
+        for i in 0..100u32 {
+            tree.insert(i, i % 3);
+        }
+
+        let mut size = 100;
+        for i in 0..100 {
+            let removed = tree.remove_if(&i, |value| *value == 0);
+            if i % 3 == 0 {
+                assert_eq!(Some((i, 0)), removed);
+                size -= 1;
+            } else {
+                assert_eq!(None, removed);
+                assert_eq!(Some(&(i % 3)), tree.find(&i));
+            }
+            check_tree(tree.root.as_ref().unwrap(), size);
+        }
+        assert_eq!(None, tree.remove_if(&100, |_| true));
+    }
+
+    #[test]
+    fn test_recoloring_on_insert_keeps_red_black_rules() {
+        let mut tree = RedBlack::new();
+        for (i, key) in [5, 4, 2, 1, 0, 3].into_iter().enumerate() {
+            tree.insert(key, ());
+            check_tree(tree.root.as_ref().unwrap(), i as u32 + 1);
+        }
+        // red 3 under red 2 recolors 1 red and 0, 2 black, black 4 above red 1 must not be rotated
+        assert_eq!(Some(vec![&4, &1, &2, &3]), tree.path_to(&3));
+        let root = tree.root.as_ref().unwrap();
+        let one = root.left_child.as_ref().unwrap();
+        assert_eq!(Color::Black, root.metadata);
+        assert_eq!(Color::Red, one.metadata);
+        assert_eq!(Color::Black, one.left_child.as_ref().unwrap().metadata);
+        assert_eq!(Color::Black, one.right_child.as_ref().unwrap().metadata);
+        assert_eq!(Color::Red, one.right_child.as_ref().unwrap().right_child.as_ref().unwrap().metadata);
+    }
+
+    #[test]
+    fn test_pop_first_and_last_keep_tree_balanced() {
+        let mut tree = RedBlack::new();
+        for i in 0..200u32 {
+            tree.insert(i, i);
+        }
+        let mut size = 200;
+        while size > 2 {
+            assert_eq!(Some((100 - size / 2, 100 - size / 2)), tree.pop_first());
+            assert_eq!(Some((99 + size / 2, 99 + size / 2)), tree.pop_last());
+            size -= 2;
+            check_tree(tree.root.as_ref().unwrap(), size);
+        }
+    }
+
+    #[test]
+    fn test_bulk_insert_keeps_last_value_and_balance() {
+        let mut tree = RedBlack::new();
+        for i in 0..500u32 {
+            tree.insert(i * 2, 0);
+        }
+        // every key from 0..1000 is three times in shuffled batch
+        tree.bulk_insert((0..3000u32).map(|i| ((i * 7919) % 1000, i)));
+        check_tree(tree.root.as_ref().unwrap(), 1000);
+        for key in 0..1000 {
+            let last = (0..3000).filter(|i| (i * 7919) % 1000 == key).max();
+            assert_eq!(last.as_ref(), tree.find(&key));
+        }
+
+        for size in 1..64 {
+            let mut tree = RedBlack::new();
+            tree.bulk_insert((0..size).map(|i| (i, i)));
+            check_tree(tree.root.as_ref().unwrap(), size);
+        }
+    }
+
+    fn check_tree<K: Ord, V>(tree: &Node<K, V>, expected_size: u32) {
         assert_eq!(tree.metadata, Color::Black);
         let (_, size) = check_tree_recursively(tree);
         assert_eq!(size, expected_size);
     }
 
-    fn check_tree_recursively<K: Ord, V>(tree: &Box<Node<K, V>>) -> (u32, u32) {
+    fn check_tree_recursively<K: Ord, V>(tree: &Node<K, V>) -> (u32, u32) {
         let (left, left_children) = match &tree.left_child {
             None => (1, 0),
             Some(child) => {
@@ -455,13 +840,14 @@ mod tests {
         };
 
         assert_eq!(left, right);
+        assert_eq!(tree.size as u32, right_children + left_children + 1);
 
-        return if tree.metadata == Color::Black {
+        if tree.metadata == Color::Black {
             (left + 1, right_children + left_children + 1)
         } else {
-            assert_eq!(false, tree.left_child.as_ref().is_some_and(|x| { x.metadata == Color::Red }));
-            assert_eq!(false, tree.right_child.as_ref().is_some_and(|x| { x.metadata == Color::Red }));
+            assert!(!tree.left_child.as_ref().is_some_and(|x| { x.metadata == Color::Red }));
+            assert!(!tree.right_child.as_ref().is_some_and(|x| { x.metadata == Color::Red }));
             (left, right_children + left_children + 1)
-        };
+        }
     }
 }
\ No newline at end of file